@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+
+use easymund_audio_codec::fft::{Complex, Fft};
+
+const BLOCK_SIZE: usize = 256;
+const FFT_SIZE: usize = BLOCK_SIZE * 2;
+/// The filter covers `PARTITIONS * BLOCK_SIZE` samples of the far end, about 93 ms at 44100 Hz: the delay
+/// between the speakers and the microphone and the room reverberation.
+const PARTITIONS: usize = 16;
+const STEP_SIZE: f32 = 0.5;
+/// Far end power below this level in every bin does not drive the adaptation.
+const NOISE_POWER: f32 = 1e-6;
+/// Near end louder than this part of the far end peak is taken for the local speaker talking.
+const DOUBLE_TALK_THRESHOLD: f32 = 0.5;
+const DOUBLE_TALK_HOLD_BLOCKS: usize = 16;
+
+/// Acoustic echo canceller: a partitioned block frequency domain adaptive filter. The far end signal
+/// played by the speakers is filtered by the estimated echo path and subtracted from the microphone signal.
+/// Adaptation is normalized by the far end power per frequency bin and stops while both sides talk.
+pub struct EchoCanceller {
+    fft: Fft,
+    near_block: Vec<f32>,
+    far_block: Vec<f32>,
+    previous_far_block: Vec<f32>,
+    far_spectra: VecDeque<Vec<Complex>>,
+    far_peaks: VecDeque<f32>,
+    weights: Vec<Vec<Complex>>,
+    constrained_partition: usize,
+    double_talk_hold: usize,
+}
+
+impl EchoCanceller {
+    pub fn create() -> Self {
+        Self {
+            fft: Fft::new(FFT_SIZE),
+            near_block: Vec::with_capacity(BLOCK_SIZE),
+            far_block: Vec::with_capacity(BLOCK_SIZE),
+            previous_far_block: vec![0.0; BLOCK_SIZE],
+            far_spectra: (0..PARTITIONS).map(|_| vec![Complex::default(); FFT_SIZE]).collect(),
+            far_peaks: VecDeque::from(vec![0.0; PARTITIONS]),
+            weights: vec![vec![Complex::default(); FFT_SIZE]; PARTITIONS],
+            constrained_partition: 0,
+            double_talk_hold: 0,
+        }
+    }
+
+    /// Takes the microphone samples and the far end samples played at the same time, missing far end samples
+    /// are taken for silence. The microphone signal without the echo is appended to the output block by block.
+    pub fn process(&mut self, near: &[f32], far: &[f32], output: &mut VecDeque<f32>) {
+        for (i, v) in near.iter().enumerate() {
+            self.near_block.push(*v);
+            self.far_block.push(far.get(i).copied().unwrap_or_default());
+            if self.near_block.len() == BLOCK_SIZE {
+                self.process_block(output);
+                self.near_block.clear();
+                self.previous_far_block = std::mem::replace(&mut self.far_block, Vec::with_capacity(BLOCK_SIZE));
+            }
+        }
+    }
+
+    fn process_block(&mut self, output: &mut VecDeque<f32>) {
+        let mut far_spectrum: Vec<Complex> = self.previous_far_block.iter().chain(self.far_block.iter())
+            .map(|v| Complex::new(*v, 0.0))
+            .collect();
+        self.fft.forward(&mut far_spectrum);
+        self.far_spectra.pop_back();
+        self.far_spectra.push_front(far_spectrum);
+        self.far_peaks.pop_back();
+        self.far_peaks.push_front(peak(&self.far_block));
+
+        let mut echo_spectrum = vec![Complex::default(); FFT_SIZE];
+        for (weights, far_spectrum) in self.weights.iter().zip(self.far_spectra.iter()) {
+            for (bin, echo) in echo_spectrum.iter_mut().enumerate() {
+                *echo = *echo + weights[bin] * far_spectrum[bin];
+            }
+        }
+        self.fft.inverse(&mut echo_spectrum);
+        let error: Vec<f32> = self.near_block.iter().zip(&echo_spectrum[BLOCK_SIZE..])
+            .map(|(near, echo)| near - echo.re)
+            .collect();
+        output.extend(&error);
+
+        if self.is_double_talk() {
+            return;
+        }
+        self.adapt(&error);
+    }
+
+    /// Geigel detector: the echo can not be louder than the far end, so a louder near end means local speech.
+    /// Adaptation is also skipped without the far end, there is no echo to learn from.
+    fn is_double_talk(&mut self) -> bool {
+        let far_peak = self.far_peaks.iter().copied().fold(0.0, f32::max);
+        if peak(&self.near_block) > DOUBLE_TALK_THRESHOLD * far_peak {
+            self.double_talk_hold = DOUBLE_TALK_HOLD_BLOCKS;
+        } else if self.double_talk_hold > 0 {
+            self.double_talk_hold -= 1;
+        }
+        self.double_talk_hold > 0 || far_peak * far_peak < NOISE_POWER
+    }
+
+    fn adapt(&mut self, error: &[f32]) {
+        let mut error_spectrum: Vec<Complex> = std::iter::repeat_n(0.0, BLOCK_SIZE).chain(error.iter().copied())
+            .map(|v| Complex::new(v, 0.0))
+            .collect();
+        self.fft.forward(&mut error_spectrum);
+        let regularization = NOISE_POWER * FFT_SIZE as f32 * PARTITIONS as f32;
+        let steps: Vec<Complex> = (0..FFT_SIZE).map(|bin| {
+            let power: f32 = self.far_spectra.iter().map(|spectrum| spectrum[bin].norm_sqr()).sum();
+            error_spectrum[bin].scale(STEP_SIZE / (power + regularization))
+        }).collect();
+        for (weights, far_spectrum) in self.weights.iter_mut().zip(self.far_spectra.iter()) {
+            for (bin, weight) in weights.iter_mut().enumerate() {
+                *weight = *weight + far_spectrum[bin].conj() * steps[bin];
+            }
+        }
+        self.constrain(self.constrained_partition);
+        self.constrained_partition = (self.constrained_partition + 1) % PARTITIONS;
+    }
+
+    /// Keeps the partition a linear convolution by zeroing the second half of its impulse response. One partition
+    /// per block is enough to stop the circular convolution errors from accumulating.
+    fn constrain(&mut self, partition: usize) {
+        let weights = &mut self.weights[partition];
+        self.fft.inverse(weights);
+        weights[BLOCK_SIZE..].iter_mut().for_each(|v| *v = Complex::default());
+        weights[..BLOCK_SIZE].iter_mut().for_each(|v| v.im = 0.0);
+        self.fft.forward(weights);
+    }
+}
+
+fn peak(block: &[f32]) -> f32 {
+    block.iter().fold(0.0, |peak, v| peak.max(v.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+    /// Size of the AudioWorklet render quantum.
+    const QUANTUM: usize = 128;
+
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+        }
+    }
+
+    /// Speech-like signal: low-passed noise in syllables of 200 ms separated by 100 ms gaps.
+    fn speech(seconds: f32, level: f32, seed: u32) -> Vec<f32> {
+        let mut noise = Noise(seed);
+        let mut value = 0.0;
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|i| {
+            value += 0.2 * (noise.next() - value);
+            let syllable = (i * 1000 / SAMPLE_RATE + seed as usize * 37) % 300;
+            if syllable < 200 { value * level * 4.0 } else { 0.0 }
+        }).collect()
+    }
+
+    /// Synthetic room impulse response: the direct sound after `delay_ms`, then reflections decaying
+    /// exponentially with the given RT60. The energy of the whole response is `gain` squared.
+    fn room_impulse_response(delay_ms: usize, gain: f32, rt60_ms: usize, seed: u32) -> Vec<f32> {
+        let delay = delay_ms * SAMPLE_RATE / 1000;
+        let tail = rt60_ms * SAMPLE_RATE / 1000;
+        let mut noise = Noise(seed);
+        let mut response = vec![0.0; delay + tail];
+        response[delay] = 1.0;
+        for (i, v) in response[delay + 1..].iter_mut().enumerate() {
+            let decay = 10_f32.powf(-3.0 * i as f32 / tail as f32);
+            *v = 0.1 * decay * noise.next();
+        }
+        let norm = energy(&response).sqrt();
+        response.iter().map(|v| v * gain / norm).collect()
+    }
+
+    /// Overlap-add convolution.
+    fn convolve(signal: &[f32], response: &[f32]) -> Vec<f32> {
+        let size = (response.len() * 2).next_power_of_two();
+        let block = size - response.len() + 1;
+        let fft = Fft::new(size);
+        let mut response_spectrum: Vec<Complex> = (0..size)
+            .map(|i| Complex::new(response.get(i).copied().unwrap_or_default(), 0.0))
+            .collect();
+        fft.forward(&mut response_spectrum);
+        let mut output = vec![0.0; signal.len() + size];
+        for (n, chunk) in signal.chunks(block).enumerate() {
+            let mut spectrum: Vec<Complex> = (0..size)
+                .map(|i| Complex::new(chunk.get(i).copied().unwrap_or_default(), 0.0))
+                .collect();
+            fft.forward(&mut spectrum);
+            spectrum.iter_mut().zip(&response_spectrum).for_each(|(v, h)| *v = *v * *h);
+            fft.inverse(&mut spectrum);
+            for (out, v) in output[n * block..].iter_mut().zip(&spectrum) {
+                *out += v.re;
+            }
+        }
+        output.truncate(signal.len());
+        output
+    }
+
+    /// Runs the canceller over the signals in render quanta as the processor does.
+    fn cancel(canceller: &mut EchoCanceller, near: &[f32], far: &[f32]) -> Vec<f32> {
+        let mut output = VecDeque::new();
+        for (near, far) in near.chunks(QUANTUM).zip(far.chunks(QUANTUM)) {
+            canceller.process(near, far, &mut output);
+        }
+        output.into_iter().collect()
+    }
+
+    fn energy(signal: &[f32]) -> f32 {
+        signal.iter().map(|v| v * v).sum::<f32>()
+    }
+
+    fn mic_noise(length: usize, level: f32) -> Vec<f32> {
+        let mut noise = Noise(97);
+        (0..length).map(|_| noise.next() * level).collect()
+    }
+
+    fn add(a: &[f32], b: &[f32]) -> Vec<f32> {
+        a.iter().zip(b).map(|(a, b)| a + b).collect()
+    }
+
+    /// Echo return loss enhancement over the last second in dB, the local signal of the microphone is not
+    /// taken for the residual echo.
+    fn erle(echo: &[f32], output: &[f32], local: Option<&[f32]>) -> f32 {
+        let length = output.len();
+        let start = length - SAMPLE_RATE;
+        let residual: Vec<f32> = (start..length)
+            .map(|i| output[i] - local.map(|local| local[i]).unwrap_or_default())
+            .collect();
+        10.0 * (energy(&echo[start..length]) / energy(&residual).max(1e-12)).log10()
+    }
+
+    #[test]
+    fn test_echo_is_cancelled() {
+        let far = speech(6.0, 0.2, 7);
+        let echo = convolve(&far, &room_impulse_response(20, 0.3, 40, 11));
+        let noise = mic_noise(far.len(), 0.003);
+        let output = cancel(&mut EchoCanceller::create(), &add(&echo, &noise), &far);
+        let erle = erle(&echo, &output, Some(&noise));
+        assert!(erle > 20.0, "ERLE {} dB", erle);
+    }
+
+    #[test]
+    fn test_long_delay_is_cancelled() {
+        let far = speech(6.0, 0.2, 5);
+        let echo = convolve(&far, &room_impulse_response(55, 0.25, 25, 13));
+        let output = cancel(&mut EchoCanceller::create(), &echo, &far);
+        let erle = erle(&echo, &output, None);
+        assert!(erle > 15.0, "ERLE {} dB", erle);
+    }
+
+    #[test]
+    fn test_near_speech_is_kept_during_double_talk() {
+        let far = speech(8.0, 0.2, 3);
+        let echo = convolve(&far, &room_impulse_response(20, 0.3, 40, 17));
+        let mut near_speech = vec![0.0; far.len()];
+        let speech_start = 5 * SAMPLE_RATE;
+        near_speech[speech_start..].copy_from_slice(&speech(3.0, 0.2, 9));
+        let local = add(&near_speech, &mic_noise(far.len(), 0.003));
+        let output = cancel(&mut EchoCanceller::create(), &add(&echo, &local), &far);
+
+        let erle = erle(&echo, &output, Some(&local));
+        assert!(erle > 15.0, "ERLE {} dB", erle);
+        let end = output.len();
+        let distortion: Vec<f32> = (speech_start..end).map(|i| output[i] - local[i]).collect();
+        let speech_to_distortion = 10.0 * (energy(&near_speech[speech_start..end]) / energy(&distortion)).log10();
+        assert!(speech_to_distortion > 15.0, "speech to distortion {} dB", speech_to_distortion);
+    }
+
+    #[test]
+    fn test_echo_path_change() {
+        let far = speech(10.0, 0.2, 21);
+        let change = 5 * SAMPLE_RATE;
+        let first = convolve(&far[..change], &room_impulse_response(20, 0.3, 40, 23));
+        let second = convolve(&far, &room_impulse_response(35, 0.2, 60, 29));
+        let echo: Vec<f32> = first.into_iter().chain(second[change..].iter().copied()).collect();
+        let output = cancel(&mut EchoCanceller::create(), &echo, &far);
+        let erle = erle(&echo, &output, None);
+        assert!(erle > 15.0, "ERLE {} dB", erle);
+    }
+
+    #[test]
+    fn test_near_speech_without_far_end_is_unchanged() {
+        let near = speech(2.0, 0.2, 31);
+        let far = vec![0.0; near.len()];
+        let output = cancel(&mut EchoCanceller::create(), &near, &far);
+        assert_eq!(output.len(), near.len() / BLOCK_SIZE * BLOCK_SIZE);
+        for (a, b) in output.iter().zip(&near) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod aec;
 mod processor;
 
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -24,7 +25,11 @@ impl WasmLib {
         self.processor.send(output)
     }
 
-    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> bool {
-        self.processor.process(input, output)
+    pub fn set_echo_cancellation(&mut self, is_enabled: bool) {
+        self.processor.set_echo_cancellation(is_enabled);
+    }
+
+    pub fn process(&mut self, input: &[f32], output_left: &mut [f32], output_right: &mut [f32]) -> bool {
+        self.processor.process(input, output_left, output_right)
     }
 }
@@ -1,11 +1,13 @@
 use std::collections::VecDeque;
 use easymund_audio_codec::codec::{Codec, EasymundAudio};
+use crate::aec::EchoCanceller;
 
 pub struct Processor {
     packet_size: usize,
     buffer_in: VecDeque<f32>,
-    buffer_out: VecDeque<f32>,
+    buffer_out: VecDeque<[f32; 2]>,
     codec: Codec,
+    echo_canceller: Option<EchoCanceller>,
 }
 
 impl Processor {
@@ -15,24 +17,34 @@ impl Processor {
         for _i in 0..packet_size / 2 {
             buffer_in.push_front(0.0);
         }
-        let easymund_audio = EasymundAudio::new(44100, 1, 16);
+        let easymund_audio = EasymundAudio::with_channels(44100, 2, 1, 16);
         Self {
             packet_size,
             buffer_in,
             buffer_out: VecDeque::with_capacity(packet_size * 2),
             codec: easymund_audio.create_codec(packet_size).unwrap(),
+            echo_canceller: None,
         }
     }
 
     pub fn receive(&mut self, input: &[u8]) {
         match self.codec.decode(input) {
-            Ok(decoded) => {
-                for v in decoded[0].iter().copied() {
-                    self.buffer_out.push_back(v);
+            Ok(decoded) => match decoded.as_slice() {
+                [left, right, ..] => {
+                    for (l, r) in left.iter().zip(right.iter()) {
+                        self.buffer_out.push_back([*l, *r]);
+                    }
                 }
-            }
-            Err(e) => {
-                let message = format!("Failed to decode: {:?}", e);
+                // Mono packets are sent before the server switches the client to stereo
+                [mono] => {
+                    for v in mono {
+                        self.buffer_out.push_back([*v, *v]);
+                    }
+                }
+                [] => {}
+            },
+            Err(_) => {
+                // Broken packet is dropped, playback continues with the next one
             }
         }
     }
@@ -48,19 +60,30 @@ impl Processor {
                 output[..encoded.len()].copy_from_slice(encoded.as_slice());
                 encoded.len()
             }
-            Err(e) => {
-                let message = format!("Failed to encode: {:?}", e);
-                0
-            }
+            Err(_) => 0
         }
     }
 
-    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> bool {
-        for v in input.iter().copied() {
-            self.buffer_in.push_back(v);
+    /// Enabling starts the echo canceller with an empty echo path, it adapts within a few seconds of the room audio.
+    pub fn set_echo_cancellation(&mut self, is_enabled: bool) {
+        self.echo_canceller = if is_enabled { Some(EchoCanceller::create()) } else { None };
+    }
+
+    pub fn process(&mut self, input: &[f32], output_left: &mut [f32], output_right: &mut [f32]) -> bool {
+        for (l, r) in output_left.iter_mut().zip(output_right.iter_mut()) {
+            [*l, *r] = self.buffer_out.pop_front().unwrap_or_default();
         }
-        for i in 0..output.len() {
-            output[i] = self.buffer_out.pop_front().unwrap_or_default();
+        match &mut self.echo_canceller {
+            Some(echo_canceller) => {
+                // Both speakers reach the microphone, the mono sum is the far end of the echo path
+                let far: Vec<f32> = output_left.iter().zip(output_right.iter()).map(|(l, r)| (l + r) / 2.0).collect();
+                echo_canceller.process(input, &far, &mut self.buffer_in);
+            }
+            None => {
+                for v in input.iter().copied() {
+                    self.buffer_in.push_back(v);
+                }
+            }
         }
         self.buffer_in.len() >= self.packet_size
     }
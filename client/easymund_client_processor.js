@@ -104,19 +104,10 @@ export class WasmLib {
         wasm.__wbg_wasmlib_free(ptr);
     }
     /**
-    * @returns {WasmLib}
-    */
-    static create() {
-        const ret = wasm.wasmlib_create();
-        return WasmLib.__wrap(ret);
-    }
-    /**
-    * @param {Uint8Array} input
+    * @param {boolean} is_enabled
     */
-    receive(input) {
-        const ptr0 = passArray8ToWasm0(input, wasm.__wbindgen_malloc);
-        const len0 = WASM_VECTOR_LEN;
-        wasm.wasmlib_receive(this.__wbg_ptr, ptr0, len0);
+    set_echo_cancellation(is_enabled) {
+        wasm.wasmlib_set_echo_cancellation(this.__wbg_ptr, is_enabled);
     }
     /**
     * @param {Uint8Array} output
@@ -129,18 +120,36 @@ export class WasmLib {
         return ret >>> 0;
     }
     /**
+    * @returns {WasmLib}
+    */
+    static create() {
+        const ret = wasm.wasmlib_create();
+        return WasmLib.__wrap(ret);
+    }
+    /**
     * @param {Float32Array} input
-    * @param {Float32Array} output
+    * @param {Float32Array} output_left
+    * @param {Float32Array} output_right
     * @returns {boolean}
     */
-    process(input, output) {
+    process(input, output_left, output_right) {
         const ptr0 = passArrayF32ToWasm0(input, wasm.__wbindgen_malloc);
         const len0 = WASM_VECTOR_LEN;
-        var ptr1 = passArrayF32ToWasm0(output, wasm.__wbindgen_malloc);
+        var ptr1 = passArrayF32ToWasm0(output_left, wasm.__wbindgen_malloc);
         var len1 = WASM_VECTOR_LEN;
-        const ret = wasm.wasmlib_process(this.__wbg_ptr, ptr0, len0, ptr1, len1, addHeapObject(output));
+        var ptr2 = passArrayF32ToWasm0(output_right, wasm.__wbindgen_malloc);
+        var len2 = WASM_VECTOR_LEN;
+        const ret = wasm.wasmlib_process(this.__wbg_ptr, ptr0, len0, ptr1, len1, addHeapObject(output_left), ptr2, len2, addHeapObject(output_right));
         return ret !== 0;
     }
+    /**
+    * @param {Uint8Array} input
+    */
+    receive(input) {
+        const ptr0 = passArray8ToWasm0(input, wasm.__wbindgen_malloc);
+        const len0 = WASM_VECTOR_LEN;
+        wasm.wasmlib_receive(this.__wbg_ptr, ptr0, len0);
+    }
 }
 
 async function __wbg_load(module, imports) {
@@ -39,8 +39,7 @@ class WasmProcessor extends AudioWorkletProcessor {
         const output_channel_1 = output[1];
 
         if (this.lib) {
-            const send = this.lib.process(input_channel, output_channel_0);
-            output_channel_1.set(output_channel_0);
+            const send = this.lib.process(input_channel, output_channel_0, output_channel_1);
             if (send) {
                 const buffer = new Uint8Array(4096);
                 const size = this.lib.send(buffer);
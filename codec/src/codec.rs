@@ -11,7 +11,8 @@ use symphonia::default::codecs::FlacDecoder;
 
 pub struct EasymundAudio {
     sample_rate: usize,
-    channels: u8,
+    decode_channels: u8,
+    encode_channels: u8,
     bits_per_sample: u8,
 }
 
@@ -26,24 +27,38 @@ pub struct Codec {
 }
 
 impl Codec {
+    /// Decodes a frame into the channels it carries, which can be fewer than the decoder is created for,
+    /// e.g. a mono frame sent to a stereo client.
     pub fn decode(&mut self, data: &[u8]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
         let packet = Packet::new_from_slice(0, 0, 0, data);
         let buffer_ref = self.decoder.decode(&packet)?;
         let mut buffer: AudioBuffer<f32> = buffer_ref.make_equivalent();
         buffer_ref.convert(&mut buffer);
-        let mut channels = Vec::with_capacity(self.channels as usize);
-        for i in 0..self.channels as usize {
+        let channels_count = Codec::frame_channels(data).unwrap_or(usize::MAX).min(buffer.spec().channels.count());
+        let mut channels = Vec::with_capacity(channels_count);
+        for i in 0..channels_count {
             let decoded_data = buffer.chan(i);
             channels.push(Vec::from(decoded_data));
         }
         Ok(channels)
     }
 
+    /// Channels count from the channel assignment of the FLAC frame header.
+    fn frame_channels(data: &[u8]) -> Option<usize> {
+        let assignment = data.get(3)? >> 4;
+        match assignment {
+            0..=7 => Some(assignment as usize + 1),
+            8..=10 => Some(2),
+            _ => None,
+        }
+    }
+
     pub fn encode(&mut self, data: &[&[f32]]) -> Result<Vec<u8>, Box<dyn Error>> {
         let mut interleaved_data = Vec::with_capacity(self.channels as usize * self.packet_size as usize);
         for i in 0..self.packet_size as usize {
-            for channel in data {
-                interleaved_data.push(if i < channel.len() { (channel[i] * i16::MAX as f32) as i32 } else { 0 });
+            for c in 0..self.channels as usize {
+                let value = data.get(c).and_then(|channel| channel.get(i)).copied().unwrap_or_default();
+                interleaved_data.push((value * i16::MAX as f32) as i32);
             }
         }
         self.frame_buf.fill_interleaved(interleaved_data.as_slice())?;
@@ -59,23 +74,28 @@ impl Codec {
 
 impl EasymundAudio {
     pub fn new(sample_rate: usize, channels: u8, bits_per_sample: u8) -> Self {
-        Self {sample_rate, channels, bits_per_sample}
+        Self::with_channels(sample_rate, channels, channels, bits_per_sample)
+    }
+
+    /// Codec receiving and sending different number of channels, e.g. mono microphone and stereo playback.
+    pub fn with_channels(sample_rate: usize, decode_channels: u8, encode_channels: u8, bits_per_sample: u8) -> Self {
+        Self {sample_rate, decode_channels, encode_channels, bits_per_sample}
     }
 
     pub fn create_codec(&self, packet_size: usize) -> Result<Codec, Box<dyn Error>> {
         let packet_size = packet_size as u16;
         let (encoder_config, stream_info, frame_buf) = self.create_encoder(packet_size)?;
         let decoder = self.create_decoder(packet_size)?;
-        Ok(Codec {decoder, encoder_config, stream_info, frame_buf, packet_size, channels: self.channels, frame_number: 0})
+        Ok(Codec {decoder, encoder_config, stream_info, frame_buf, packet_size, channels: self.encode_channels, frame_number: 0})
     }
 
-    fn write_flac_stream_info<S: BitSink>(&self, packet_size: u16, dest: &mut S) -> Result<(), Box<dyn Error>> {
+    fn write_flac_stream_info<S: BitSink>(&self, packet_size: u16, channels: u8, dest: &mut S) -> Result<(), Box<dyn Error>> {
         dest.write::<u16>(packet_size).map_err(|e| format!("{:?}", e))?;
         dest.write::<u16>(packet_size).map_err(|e| format!("{:?}", e))?;
         dest.write_lsbs(1024_u32, 24).map_err(|e| format!("{:?}", e))?;
         dest.write_lsbs(8192_u32, 24).map_err(|e| format!("{:?}", e))?;
         dest.write_lsbs(self.sample_rate as u32, 20).map_err(|e| format!("{:?}", e))?;
-        dest.write_lsbs(self.channels - 1, 3).map_err(|e| format!("{:?}", e))?;
+        dest.write_lsbs(channels - 1, 3).map_err(|e| format!("{:?}", e))?;
         dest.write_lsbs(self.bits_per_sample - 1, 5).map_err(|e| format!("{:?}", e))?;
         dest.write_lsbs(0_u64, 36).map_err(|e| format!("{:?}", e))?;
         dest.write_bytes_aligned(&[0; 16]).map_err(|e| format!("{:?}", e))?;
@@ -84,7 +104,7 @@ impl EasymundAudio {
 
     fn create_decoder(&self, packet_size: u16) -> Result<FlacDecoder, Box<dyn Error>> {
         let mut format_sink = MemSink::new();
-        self.write_flac_stream_info(packet_size, &mut format_sink)?;
+        self.write_flac_stream_info(packet_size, self.decode_channels, &mut format_sink)?;
         let format_bytes = format_sink.into_inner();
         let codec_params = CodecParameters {
             codec: CODEC_TYPE_FLAC,
@@ -98,10 +118,10 @@ impl EasymundAudio {
     fn create_encoder(&self, packet_size: u16) -> Result<(Encoder, StreamInfo, FrameBuf), Box<dyn Error>> {
         let mut encoder_config = Encoder::default();
         encoder_config.block_sizes = vec![packet_size as usize];
-        let stream_info = StreamInfo::new(self.sample_rate as usize,
-                                          self.channels as usize,
+        let stream_info = StreamInfo::new(self.sample_rate,
+                                          self.encode_channels as usize,
                                           self.bits_per_sample as usize);
-        let frame_buf = FrameBuf::with_size(self.channels as usize,
+        let frame_buf = FrameBuf::with_size(self.encode_channels as usize,
                                                              packet_size as usize);
         Ok((encoder_config, stream_info, frame_buf))
     }
@@ -0,0 +1,137 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul};
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self {re, im}
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn scale(self, factor: f32) -> Complex {
+        Complex::new(self.re * factor, self.im * factor)
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+/// Iterative radix-2 FFT of a fixed power of two size.
+pub struct Fft {
+    size: usize,
+    twiddles: Vec<Complex>,
+}
+
+impl Fft {
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two(), "FFT size must be a power of two");
+        let twiddles = (0..size / 2)
+            .map(|i| {
+                let angle = -2.0 * PI * i as f32 / size as f32;
+                Complex::new(angle.cos(), angle.sin())
+            })
+            .collect();
+        Self {size, twiddles}
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn forward(&self, data: &mut [Complex]) {
+        self.transform(data, false);
+    }
+
+    /// Inverse transform scaled by `1 / size`.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        self.transform(data, true);
+        let scale = 1.0 / self.size as f32;
+        data.iter_mut().for_each(|v| *v = v.scale(scale));
+    }
+
+    fn transform(&self, data: &mut [Complex], is_inverse: bool) {
+        let bits = self.size.trailing_zeros();
+        for i in 0..self.size {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            if j > i {
+                data.swap(i, j);
+            }
+        }
+        let mut length = 2;
+        while length <= self.size {
+            let step = self.size / length;
+            for start in (0..self.size).step_by(length) {
+                for k in 0..length / 2 {
+                    let twiddle = self.twiddles[k * step];
+                    let twiddle = if is_inverse { twiddle.conj() } else { twiddle };
+                    let odd = data[start + k + length / 2] * twiddle;
+                    let even = data[start + k];
+                    data[start + k] = even + odd;
+                    data[start + k + length / 2] = even + odd.scale(-1.0);
+                }
+            }
+            length *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    const SIZE: usize = 512;
+
+    #[test]
+    fn test_fft_round_trip() {
+        let fft = Fft::new(SIZE);
+        let mut rng = rand::thread_rng();
+        let signal: Vec<Complex> = (0..SIZE).map(|_| Complex::new(rng.gen_range(-1.0..1.0), 0.0)).collect();
+        let mut spectrum = signal.clone();
+        fft.forward(&mut spectrum);
+        fft.inverse(&mut spectrum);
+        for (a, b) in signal.iter().zip(&spectrum) {
+            assert!((a.re - b.re).abs() < 1e-5 && b.im.abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_fft_tone() {
+        let fft = Fft::new(SIZE);
+        let bin = 10;
+        let mut spectrum: Vec<Complex> = (0..SIZE)
+            .map(|i| Complex::new((2.0 * PI * bin as f32 * i as f32 / SIZE as f32).cos(), 0.0))
+            .collect();
+        fft.forward(&mut spectrum);
+        for (i, v) in spectrum.iter().enumerate() {
+            let expected = if i == bin || i == SIZE - bin { SIZE as f32 / 2.0 } else { 0.0 };
+            assert!((v.norm_sqr().sqrt() - expected).abs() < 1e-2, "bin {}: {:?}", i, v);
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod codec;
+pub mod fft;
+pub mod media;
 
 pub fn default_packet_size() -> usize {
     2048
@@ -8,6 +10,7 @@ pub fn default_packet_size() -> usize {
 mod tests {
     use rand::Rng;
     use crate::codec::EasymundAudio;
+    use crate::media::MediaDecoder;
 
     #[test]
     fn test_filtered_noise() {
@@ -16,7 +19,7 @@ mod tests {
         let mut codec = audio.create_codec(packet_size).expect("Codec must be created");
         let mut value = 0.0;
         for _ in 0..16 {
-            let mut packet_data = Vec::with_capacity(packet_size as usize);
+            let mut packet_data = Vec::with_capacity(packet_size);
             let mut rng = rand::thread_rng();
             for _ in 0..packet_size {
                 let rnd: f32 = rng.gen();
@@ -34,4 +37,105 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_stereo_output() {
+        let packet_size = 2048;
+        let mut server = EasymundAudio::with_channels(44100, 1, 2, 16).create_codec(packet_size).expect("Codec must be created");
+        let mut client = EasymundAudio::with_channels(44100, 2, 1, 16).create_codec(packet_size).expect("Codec must be created");
+
+        let left: Vec<f32> = (0..packet_size).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+        let right: Vec<f32> = left.iter().map(|v| v * 0.25).collect();
+        let encoded = server.encode(&[left.as_slice(), right.as_slice()]).expect("Success encode");
+        let decoded = client.decode(encoded.as_slice()).expect("Success decode");
+        assert_eq!(decoded.len(), 2);
+        for i in 0..packet_size {
+            assert!((decoded[0][i] - left[i]).abs() < 2.0 / i16::MAX as f32);
+            assert!((decoded[1][i] - right[i]).abs() < 2.0 / i16::MAX as f32);
+        }
+
+        let mono: Vec<f32> = left.iter().map(|v| -v).collect();
+        let encoded = client.encode(&[mono.as_slice()]).expect("Success encode");
+        let decoded = server.decode(encoded.as_slice()).expect("Success decode");
+        assert_eq!(decoded.len(), 1);
+        for (i, v) in mono.iter().enumerate() {
+            assert!((decoded[0][i] - v).abs() < 2.0 / i16::MAX as f32);
+        }
+    }
+
+    #[test]
+    fn test_mono_frame_to_stereo_client() {
+        let packet_size = 2048;
+        let mut server = EasymundAudio::new(44100, 1, 16).create_codec(packet_size).expect("Codec must be created");
+        let mut client = EasymundAudio::with_channels(44100, 2, 1, 16).create_codec(packet_size).expect("Codec must be created");
+
+        let mono: Vec<f32> = (0..packet_size).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+        let encoded = server.encode(&[mono.as_slice()]).expect("Success encode");
+        let decoded = client.decode(encoded.as_slice()).expect("Success decode");
+        assert_eq!(decoded.len(), 1);
+        for (i, v) in mono.iter().enumerate() {
+            assert!((decoded[0][i] - v).abs() < 2.0 / i16::MAX as f32);
+        }
+    }
+
+    fn wav_bytes(sample_rate: u32, channels: &[Vec<i16>]) -> Vec<u8> {
+        let frames = channels[0].len();
+        let block_align = 2 * channels.len() as u16;
+        let data_size = frames as u32 * block_align as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16_u32.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes());
+        bytes.extend_from_slice(&(channels.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&16_u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        for i in 0..frames {
+            for channel in channels {
+                bytes.extend_from_slice(&channel[i].to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        let left: Vec<i16> = (0..4410).map(|i| ((i as f32 * 0.05).sin() * 16384.0) as i16).collect();
+        let right: Vec<i16> = left.iter().map(|v| -v).collect();
+        let wav = wav_bytes(44100, &[left.clone(), right]);
+
+        let decoded = MediaDecoder::new(44100).decode_bytes(wav, Some("wav")).expect("Success decode");
+        assert_eq!(decoded.channels_count(), 2);
+        assert_eq!(decoded.frames(), left.len());
+        for (i, v) in left.iter().enumerate() {
+            assert!((decoded.channels[0][i] - *v as f32 / 32768.0).abs() < 1e-4);
+            assert!((decoded.channels[1][i] + *v as f32 / 32768.0).abs() < 1e-4);
+        }
+        assert!(decoded.to_mono().iter().all(|v| v.abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_decode_resample() {
+        let samples: Vec<i16> = (0..2205).map(|i| ((i as f32 * 0.01).sin() * 16384.0) as i16).collect();
+        let wav = wav_bytes(22050, &[samples]);
+
+        let decoded = MediaDecoder::new(44100).decode_bytes(wav, None).expect("Success decode");
+        assert_eq!(decoded.sample_rate, 44100);
+        assert_eq!(decoded.channels_count(), 1);
+        assert_eq!(decoded.frames(), 4410);
+        for (i, v) in decoded.channels[0].iter().enumerate().take(4408) {
+            let expected = (i as f32 * 0.005).sin() * 0.5;
+            assert!((v - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(MediaDecoder::new(44100).decode_bytes(vec![0; 1024], None).is_err());
+    }
 }
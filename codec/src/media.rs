@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
+use symphonia::core::codecs::{CODEC_TYPE_NULL, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Decoded audio in planar layout, one `Vec` per channel, resampled to the decoder sample rate.
+pub struct DecodedAudio {
+    pub sample_rate: usize,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl DecodedAudio {
+    pub fn channels_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn frames(&self) -> usize {
+        self.channels.first().map(|channel| channel.len()).unwrap_or_default()
+    }
+
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels_count = self.channels_count();
+        if channels_count == 1 {
+            return self.channels[0].clone();
+        }
+        let mut result = Vec::with_capacity(self.frames());
+        for i in 0..self.frames() {
+            let sum: f32 = self.channels.iter().map(|channel| channel[i]).sum();
+            result.push(sum / channels_count as f32);
+        }
+        result
+    }
+}
+
+/// Decodes media files and streams in any format known to symphonia (WAV, FLAC, OGG/Vorbis, MP3).
+pub struct MediaDecoder {
+    sample_rate: usize,
+}
+
+impl MediaDecoder {
+    pub fn new(sample_rate: usize) -> Self {
+        Self {sample_rate}
+    }
+
+    pub fn decode_file(&self, path: &Path) -> Result<DecodedAudio, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let extension = path.extension().and_then(|e| e.to_str());
+        self.decode_source(Box::new(file), extension)
+    }
+
+    pub fn decode_bytes(&self, data: Vec<u8>, extension: Option<&str>) -> Result<DecodedAudio, Box<dyn Error>> {
+        self.decode_source(Box::new(Cursor::new(data)), extension)
+    }
+
+    pub fn decode_source(&self, source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<DecodedAudio, Box<dyn Error>> {
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+        let stream = MediaSourceStream::new(source, Default::default());
+        let probed = symphonia::default::get_probe()
+            .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())?;
+        let mut format = probed.format;
+        let track = format.tracks().iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or("No audio track")?;
+        let track_id = track.id;
+        let source_rate = track.codec_params.sample_rate.ok_or("Unknown sample rate")? as usize;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut channels: Vec<Vec<f32>> = Vec::new();
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(Box::new(e)),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            match decoder.decode(&packet) {
+                Ok(buffer) => MediaDecoder::append_planar(&mut channels, buffer),
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+        if channels.is_empty() {
+            return Err("No audio data decoded")?;
+        }
+
+        let channels = channels.iter()
+            .map(|channel| MediaDecoder::resample(channel, source_rate, self.sample_rate))
+            .collect();
+        Ok(DecodedAudio {sample_rate: self.sample_rate, channels})
+    }
+
+    fn append_planar(channels: &mut Vec<Vec<f32>>, buffer: AudioBufferRef) {
+        let spec = *buffer.spec();
+        let channels_count = spec.channels.count();
+        if channels.is_empty() {
+            channels.resize(channels_count, Vec::new());
+        }
+        let mut samples = SampleBuffer::<f32>::new(buffer.capacity() as u64, spec);
+        samples.copy_planar_ref(buffer);
+        let frames = samples.len() / channels_count;
+        for (i, channel) in channels.iter_mut().enumerate().take(channels_count) {
+            channel.extend_from_slice(&samples.samples()[i * frames..(i + 1) * frames]);
+        }
+    }
+
+    fn resample(data: &[f32], from_rate: usize, to_rate: usize) -> Vec<f32> {
+        if from_rate == to_rate || data.is_empty() {
+            return Vec::from(data);
+        }
+        let length = data.len() * to_rate / from_rate;
+        let step = from_rate as f64 / to_rate as f64;
+        let mut result = Vec::with_capacity(length);
+        for i in 0..length {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let fraction = (pos - index as f64) as f32;
+            let v0 = data[index];
+            let v1 = if index + 1 < data.len() { data[index + 1] } else { v0 };
+            result.push(v0 + (v1 - v0) * fraction);
+        }
+        result
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ActivityConfig {
+    /// Inactivity after which the participant is suggested to deafen, 0 disables the suggestion.
+    pub deafen_suggestion_ms: u64,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {deafen_suggestion_ms: 600_000}
+    }
+}
+
+/// Last time the participant talked or sent a command.
+pub struct Activity {
+    last_active_at: Instant,
+    is_deafen_suggested: bool,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Self {last_active_at: Instant::now(), is_deafen_suggested: false}
+    }
+
+    pub fn touch(&mut self) {
+        self.last_active_at = Instant::now();
+        self.is_deafen_suggested = false;
+    }
+
+    /// Returns `true` once per inactivity period when the participant has been inactive for too long.
+    pub fn check_deafen_suggestion(&mut self, config: &ActivityConfig) -> bool {
+        if config.deafen_suggestion_ms == 0 || self.is_deafen_suggested {
+            return false;
+        }
+        self.is_deafen_suggested = self.last_active_at.elapsed() >= Duration::from_millis(config.deafen_suggestion_ms);
+        self.is_deafen_suggested
+    }
+}
@@ -1,45 +1,323 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fs;
 use std::fs::File;
-use std::path::PathBuf;
-use log::info;
+use std::path::Path;
+
+use log::{error, info};
+use serde::Deserialize;
+
+use easymund_audio_codec::media::MediaDecoder;
+
+use crate::easymund::SAMPLE_RATE;
+use crate::procedural::{GeneratorParams, ProceduralGenerator};
+
+const MANIFEST_FILE: &str = "ambiences.yaml";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AmbienceConfig {
+    pub sounds_path: String,
+    pub loop_crossfade_ms: u64,
+    pub switch_crossfade_ms: u64,
+    pub ducking: DuckingConfig,
+}
+
+impl Default for AmbienceConfig {
+    fn default() -> Self {
+        Self {
+            sounds_path: String::from("sounds"),
+            loop_crossfade_ms: 500,
+            switch_crossfade_ms: 2000,
+            ducking: DuckingConfig::default(),
+        }
+    }
+}
+
+/// Default ambience ducking settings of new rooms.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DuckingConfig {
+    pub enabled: bool,
+    pub depth_db: f32,
+    pub attack_ms: u64,
+    pub release_ms: u64,
+}
+
+impl Default for DuckingConfig {
+    fn default() -> Self {
+        Self {enabled: true, depth_db: 12.0, attack_ms: 150, release_ms: 1500}
+    }
+}
+
+impl AmbienceConfig {
+    pub fn loop_crossfade(&self, sample_rate: usize) -> usize {
+        self.loop_crossfade_ms as usize * sample_rate / 1000
+    }
+
+    pub fn switch_crossfade(&self, sample_rate: usize) -> usize {
+        self.switch_crossfade_ms as usize * sample_rate / 1000
+    }
+}
 
 pub struct Ambience {
     pub id: String,
     pub name: String,
+    pub names: HashMap<String, String>,
+    pub category: String,
+    pub volume: f32,
+    pub loop_start: usize,
+    pub loop_end: usize,
     pub data: Vec<f32>,
+    pub generator: Option<GeneratorParams>,
+}
+
+#[derive(Deserialize)]
+struct AmbienceManifest {
+    default_language: String,
+    /// Entries are parsed one by one, so a malformed entry does not fail the whole manifest.
+    ambiences: Vec<serde_yaml::Value>,
+}
+
+#[derive(Deserialize)]
+struct AmbienceEntry {
+    id: String,
+    name: HashMap<String, String>,
+    file: Option<String>,
+    volume: Option<f32>,
+    loop_start: Option<f32>,
+    loop_end: Option<f32>,
+    category: Option<String>,
+    generator: Option<GeneratorParams>,
 }
 
 impl Ambience {
-    pub fn read_dir(path: &str) -> Result<Vec<Ambience>, Box<dyn Error>> {
-        let mut result = Vec::new();
-        for entry in fs::read_dir(path)?.flatten() {
-            let filename = entry.file_name().into_string().map_err(|s| format!("Invalid OsString {:?}", s))?;
-            let sound_data = Ambience::read_sound(&entry.path(), 0.5)?;
-            if let Some((id, name)) = filename.split_once('_') {
-                let (name, _) = name.split_once('.').unwrap_or((name, ""));
-                let ambience = Ambience {
-                    id: String::from(id),
-                    name: String::from(name),
-                    data: sound_data,
-                };
-                info!("Read ambience id={}, name={}, length {}", &ambience.id, &ambience.name, ambience.data.len());
-                result.push(ambience);
+    /// Reads `ambiences.yaml` manifest from the sounds dir. Invalid entries are reported and skipped,
+    /// the first valid entry is the default ambience for new rooms.
+    pub fn read_manifest(path: &str, sample_rate: usize) -> Result<Vec<Ambience>, Box<dyn Error>> {
+        let manifest_path = Path::new(path).join(MANIFEST_FILE);
+        let manifest: AmbienceManifest = serde_yaml::from_reader(File::open(&manifest_path)?)?;
+        let mut result: Vec<Ambience> = Vec::with_capacity(manifest.ambiences.len());
+        let mut ids = HashSet::new();
+        for (i, value) in manifest.ambiences.into_iter().enumerate() {
+            let entry: AmbienceEntry = match serde_yaml::from_value(value) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    error!("Invalid ambience #{} in {:?}: {}", i, &manifest_path, e);
+                    continue;
+                }
+            };
+            if !ids.insert(entry.id.clone()) {
+                error!("Invalid ambience #{} in {:?}: duplicate id {}", i, &manifest_path, &entry.id);
+                continue;
+            }
+            match Ambience::read_entry(path, &entry, &manifest.default_language, sample_rate) {
+                Ok(ambience) => {
+                    info!("Read ambience id={}, name={}, category={}, length {}",
+                        &ambience.id, &ambience.name, &ambience.category, ambience.data.len());
+                    result.push(ambience);
+                }
+                Err(e) => {
+                    error!("Invalid ambience #{} id={} in {:?}: {}", i, &entry.id, &manifest_path, e);
+                }
             }
         }
-        result.sort_by(|a0, a1| a0.id.cmp(&a1.id));
-        result.push(Ambience {
-            id: String::from("0"),
-            name: String::from("тишина"),
-            data: Vec::new(),
-        });
         Ok(result)
     }
 
-    fn read_sound(path: &PathBuf, factor: f32) -> Result<Vec<f32>, Box<dyn Error>> {
-        let mut background_file = File::open(path)?;
-        let (_, bits) = wav::read(&mut background_file)?;
-        let data_i16 = bits.try_into_sixteen().unwrap_or_default();
-        Ok(data_i16.iter().map(|v| *v as f32 * factor / 32768.0).collect())
+    fn read_entry(path: &str, entry: &AmbienceEntry, default_language: &str, sample_rate: usize)
+                  -> Result<Ambience, Box<dyn Error>> {
+        if entry.id.is_empty() {
+            return Err("empty id")?;
+        }
+        let name = entry.name.get(default_language)
+            .ok_or(format!("no name in default language '{}'", default_language))?;
+        let volume = entry.volume.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(format!("volume {} is out of range 0..1", volume))?;
+        }
+        if entry.file.is_some() && entry.generator.is_some() {
+            return Err("both file and generator are set")?;
+        }
+        let data = match &entry.file {
+            Some(file) => MediaDecoder::new(sample_rate).decode_file(&Path::new(path).join(file))
+                .map_err(|e| format!("failed to read {}: {}", file, e))?
+                .to_mono(),
+            None => Vec::new(),
+        };
+        let to_samples = |seconds: f32| (seconds * sample_rate as f32) as usize;
+        let loop_start = entry.loop_start.map(to_samples).unwrap_or(0);
+        let loop_end = entry.loop_end.map(to_samples).unwrap_or(data.len());
+        if loop_end > data.len() {
+            return Err(format!("loop end {} is beyond sound length {}", loop_end, data.len()))?;
+        }
+        if loop_start > loop_end || (loop_start == loop_end && !data.is_empty()) {
+            return Err(format!("loop start {} must be before loop end {}", loop_start, loop_end))?;
+        }
+        Ok(Ambience {
+            id: entry.id.clone(),
+            name: name.clone(),
+            names: entry.name.clone(),
+            category: entry.category.clone().unwrap_or_default(),
+            volume,
+            loop_start,
+            loop_end,
+            data,
+            generator: entry.generator.clone(),
+        })
+    }
+}
+
+/// Playback position of an ambience in a room.
+pub struct AmbiencePlayback {
+    pub ambience_id: String,
+    position: usize,
+}
+
+/// One ambience of a room scene. Added and removed layers fade in and out, volume changes are ramped over a chunk.
+/// Procedural ambiences are synthesised by the layer generator with parameters adjustable live.
+pub struct AmbienceLayer {
+    pub playback: AmbiencePlayback,
+    pub volume: f32,
+    pub params: HashMap<String, f32>,
+    pub is_removed: bool,
+    generator: Option<ProceduralGenerator>,
+    gain: f32,
+    presence: f32,
+}
+
+impl AmbienceLayer {
+    pub fn new(ambience_id: &str, volume: f32) -> Self {
+        Self {
+            playback: AmbiencePlayback::new(ambience_id),
+            volume,
+            params: HashMap::new(),
+            is_removed: false,
+            generator: None,
+            gain: volume,
+            presence: 0.0,
+        }
+    }
+
+    pub fn set_params(&mut self, params: &HashMap<String, f32>) {
+        for (name, value) in params {
+            if GeneratorParams::NAMES.contains(&name.as_str()) {
+                self.params.insert(name.clone(), value.clamp(0.0, 1.0));
+            } else {
+                error!("Unknown ambience parameter {}", name);
+            }
+        }
+        if let Some(generator) = &mut self.generator {
+            for (name, value) in &self.params {
+                generator.params.set(name, *value);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fn source_chunk(&mut self, ambience: &Ambience, samples_count: usize, loop_crossfade: usize) -> Vec<f32> {
+        match &ambience.generator {
+            Some(generator_params) => {
+                let generator = self.generator.get_or_insert_with(|| {
+                    let mut generator = ProceduralGenerator::new(generator_params, SAMPLE_RATE, rand::random());
+                    for (name, value) in &self.params {
+                        generator.params.set(name, *value);
+                    }
+                    generator
+                });
+                generator.next_chunk(samples_count).iter().map(|v| v * ambience.volume).collect()
+            }
+            None => self.playback.next_chunk(ambience, samples_count, loop_crossfade),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_removed && self.presence <= 0.0
+    }
+
+    pub fn next_chunk(&mut self, ambience: &Ambience, samples_count: usize, loop_crossfade: usize,
+                      switch_crossfade: usize) -> Vec<f32> {
+        let mut chunk = self.source_chunk(ambience, samples_count, loop_crossfade);
+        let presence_step = if switch_crossfade > 0 { 1.0 / switch_crossfade as f32 } else { 1.0 };
+        let presence_step = if self.is_removed { -presence_step } else { presence_step };
+        let gain_step = (self.volume - self.gain) / samples_count as f32;
+        if chunk.is_empty() {
+            self.presence = (self.presence + presence_step * samples_count as f32).clamp(0.0, 1.0);
+        }
+        for v in chunk.iter_mut() {
+            self.presence = (self.presence + presence_step).clamp(0.0, 1.0);
+            self.gain += gain_step;
+            let (_, presence_gain) = equal_power_gains(self.presence);
+            *v *= self.gain * presence_gain;
+        }
+        self.gain = self.volume;
+        chunk
+    }
+}
+
+impl AmbiencePlayback {
+    pub fn new(ambience_id: &str) -> Self {
+        Self {ambience_id: String::from(ambience_id), position: 0}
+    }
+
+    /// Renders the next chunk of the ambience. Before the loop end the tail is crossfaded with the loop start,
+    /// then playback continues right after the crossfaded part of the loop start.
+    pub fn next_chunk(&mut self, ambience: &Ambience, samples_count: usize, loop_crossfade: usize) -> Vec<f32> {
+        if ambience.data.is_empty() {
+            return Vec::new();
+        }
+        let crossfade = loop_crossfade.min((ambience.loop_end - ambience.loop_start) / 2);
+        let fade_start = ambience.loop_end - crossfade;
+        let mut chunk = Vec::with_capacity(samples_count);
+        let mut pos = self.position;
+        for _ in 0..samples_count {
+            let v = if pos >= fade_start {
+                let k = pos - fade_start;
+                let (fade_out, fade_in) = equal_power_gains((k as f32 + 0.5) / crossfade as f32);
+                ambience.data[pos] * fade_out + ambience.data[ambience.loop_start + k] * fade_in
+            } else {
+                ambience.data[pos]
+            };
+            chunk.push(v * ambience.volume);
+            pos += 1;
+            if pos >= ambience.loop_end {
+                pos = ambience.loop_start + crossfade;
+            }
+        }
+        self.position = pos;
+        chunk
+    }
+}
+
+/// Gains of fading out and fading in signals at the crossfade progress `t` in 0..1, keeping the sum power constant.
+pub fn equal_power_gains(t: f32) -> (f32, f32) {
+    let angle = t.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+    (angle.cos(), angle.sin())
+}
+
+/// Lowers the ambience level while somebody in the room is talking.
+pub struct AmbienceDucker {
+    pub config: DuckingConfig,
+    gain: f32,
+}
+
+impl AmbienceDucker {
+    pub fn new(config: &DuckingConfig) -> Self {
+        Self {config: config.clone(), gain: 1.0}
+    }
+
+    pub fn apply(&mut self, chunk: &mut [f32], is_talking: bool, sample_rate: usize) {
+        let target = if is_talking && self.config.enabled {
+            10_f32.powf(-self.config.depth_db.max(0.0) / 20.0)
+        } else { 1.0 };
+        let time_ms = if target < self.gain { self.config.attack_ms } else { self.config.release_ms };
+        let coefficient = if time_ms > 0 {
+            (-1000.0 / (time_ms as f32 * sample_rate as f32)).exp()
+        } else { 0.0 };
+        if chunk.is_empty() {
+            self.gain = target;
+        }
+        for v in chunk.iter_mut() {
+            self.gain = target + (self.gain - target) * coefficient;
+            *v *= self.gain;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use chrono::Utc;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use log::{error, info};
+
+/// Software participant attached to a room without a WebSocket connection. Every tick it may produce a chunk of
+/// its own audio and receives the mix of everybody else in the room, exactly like a connected client.
+pub trait VirtualParticipant: Send {
+    fn name(&self) -> String;
+
+    /// Audio of the participant for the current tick, `None` when it has nothing to say.
+    fn produce(&mut self, samples_count: usize) -> Option<Vec<f32>>;
+
+    /// Mix-minus of the room for the current tick.
+    fn consume(&mut self, mix: &[f32]);
+
+    /// Called once when the participant is removed from the room.
+    fn close(&mut self) {}
+}
+
+/// Plays a continuous sine tone into the room.
+pub struct TestToneBot {
+    frequency: f32,
+    level: f32,
+    sample_rate: f32,
+    phase: f32,
+}
+
+impl TestToneBot {
+    pub fn new(frequency: f32, level: f32, sample_rate: usize) -> Self {
+        Self {frequency, level, sample_rate: sample_rate as f32, phase: 0.0}
+    }
+}
+
+impl VirtualParticipant for TestToneBot {
+    fn name(&self) -> String {
+        format!("Тон {} Гц", self.frequency)
+    }
+
+    fn produce(&mut self, samples_count: usize) -> Option<Vec<f32>> {
+        let mut chunk = Vec::with_capacity(samples_count);
+        for _ in 0..samples_count {
+            chunk.push((2.0 * PI * self.phase).sin() * self.level);
+            self.phase = (self.phase + self.frequency / self.sample_rate).fract();
+        }
+        Some(chunk)
+    }
+
+    fn consume(&mut self, _: &[f32]) {}
+}
+
+/// Records what it hears in the room to a wav file.
+pub struct RecorderBot {
+    file_name: String,
+    writer: Option<WavWriter<BufWriter<File>>>,
+}
+
+impl RecorderBot {
+    pub fn new(path: &str, room_id: &str, sample_rate: usize) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(path)?;
+        let file_name = format!("{}_{}.wav", room_id, Utc::now().format("%Y%m%d_%H%M%S"));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: sample_rate as u32,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(Path::new(path).join(&file_name), spec)?;
+        info!("Recording room {} to {}", room_id, &file_name);
+        Ok(Self {file_name, writer: Some(writer)})
+    }
+}
+
+impl VirtualParticipant for RecorderBot {
+    fn name(&self) -> String {
+        format!("Запись {}", &self.file_name)
+    }
+
+    fn produce(&mut self, _: usize) -> Option<Vec<f32>> {
+        None
+    }
+
+    fn consume(&mut self, mix: &[f32]) {
+        if let Some(writer) = &mut self.writer {
+            for v in mix {
+                if let Err(e) = writer.write_sample((v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16) {
+                    error!("Failed to record {}: {:?}", &self.file_name, e);
+                    self.writer = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            match writer.finalize() {
+                Ok(_) => info!("Recording {} finished", &self.file_name),
+                Err(e) => error!("Failed to finalize recording {}: {:?}", &self.file_name, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::dto;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BreakoutConfig {
+    pub max_rooms: usize,
+    pub countdown_s: u64,
+}
+
+impl Default for BreakoutConfig {
+    fn default() -> Self {
+        Self {max_rooms: 20, countdown_s: 30}
+    }
+}
+
+/// Sub-rooms of a meeting. Participants are moved to the sub-rooms and pulled back to the parent room
+/// when the time is over.
+pub struct Breakouts {
+    /// Ids and names of the sub-rooms.
+    pub rooms: Vec<(String, String)>,
+    pub assignments: HashMap<u64, String>,
+    is_started: bool,
+    ends_at: Option<Instant>,
+    countdown: Duration,
+    last_countdown_s: Option<u64>,
+}
+
+impl Breakouts {
+    pub fn new(rooms: Vec<(String, String)>, config: &BreakoutConfig) -> Self {
+        Self {
+            rooms,
+            assignments: HashMap::new(),
+            is_started: false,
+            ends_at: None,
+            countdown: Duration::from_secs(config.countdown_s),
+            last_countdown_s: None,
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.is_started
+    }
+
+    pub fn replace_client(&mut self, old_id: u64, new_id: u64) {
+        if let Some(room_id) = self.assignments.remove(&old_id) {
+            self.assignments.insert(new_id, room_id);
+        }
+    }
+
+    /// Starts the breakout session, without the duration the sub-rooms stay open until closed.
+    pub fn start(&mut self, duration: Option<Duration>) {
+        self.is_started = true;
+        self.ends_at = duration.map(|duration| Instant::now() + duration.max(self.countdown));
+    }
+
+    /// Starts the countdown before pulling everybody back.
+    pub fn close(&mut self) {
+        let ends_at = Instant::now() + self.countdown;
+        self.ends_at = Some(self.ends_at.map_or(ends_at, |current| current.min(ends_at)));
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.ends_at.is_some_and(|ends_at| Instant::now() >= ends_at)
+    }
+
+    /// Seconds left when a new second of the final countdown begins.
+    pub fn countdown_tick(&mut self) -> Option<u64> {
+        let remaining = self.remaining()?;
+        if remaining > self.countdown {
+            return None;
+        }
+        let remaining_s = remaining.as_secs_f32().ceil() as u64;
+        if self.last_countdown_s == Some(remaining_s) {
+            return None;
+        }
+        self.last_countdown_s = Some(remaining_s);
+        Some(remaining_s)
+    }
+
+    pub fn state(&self) -> dto::Breakout {
+        let rooms = self.rooms.iter().map(|(id, name)| {
+            let mut participants: Vec<u64> = self.assignments.iter()
+                .filter(|(_, room_id)| *room_id == id)
+                .map(|(client_id, _)| *client_id)
+                .collect();
+            participants.sort();
+            dto::BreakoutRoom {id: Some(id.clone()), name: name.clone(), participants}
+        }).collect();
+        dto::Breakout {
+            rooms: Some(rooms),
+            is_started: Some(self.is_started),
+            remaining_s: self.remaining().map(|remaining| remaining.as_secs_f32().ceil() as u64),
+            ..Default::default()
+        }
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.ends_at.map(|ends_at| ends_at.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// Deals the participants out to the rooms in a random order so the groups differ at most by one.
+pub fn assign_randomly(participants: &[u64], rooms: &[String]) -> HashMap<u64, String> {
+    let mut participants = participants.to_vec();
+    participants.shuffle(&mut rand::thread_rng());
+    participants.into_iter().zip(rooms.iter().cycle()).map(|(client_id, room_id)| (client_id, room_id.clone())).collect()
+}
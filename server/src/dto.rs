@@ -1,11 +1,17 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct EasymundEvent {
     pub event: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub room_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub participant: Option<Participant>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ambience: Option<String>,
@@ -18,6 +24,51 @@ pub struct EasymundEvent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ambiences: Option<Vec<Ambience>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub scene: Option<Vec<SceneLayer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ducking: Option<Ducking>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sounds: Option<Vec<Sound>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<Media>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_files: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bot: Option<Bot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub echo: Option<Echo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_spatial: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_stage: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_channels: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub floor: Option<Floor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hands: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whisper: Option<Whisper>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakout: Option<Breakout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<Suggestion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<Warning>,
+    /// Participant id, RMS and peak level in whole dBFS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub levels: Option<Vec<(u64, i8, i8)>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
@@ -28,12 +79,147 @@ pub struct Participant {
     pub is_admin: Option<bool>,
     pub is_muted: Option<bool>,
     pub is_sharing: Option<bool>,
+    pub can_use_soundboard: Option<bool>,
+    pub is_bot: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    pub is_listener: Option<bool>,
+    pub is_reconnecting: Option<bool>,
+    pub is_deafened: Option<bool>,
+    pub is_hand_raised: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hand_position: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interpreter_channel: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Ambience {
     pub id: String,
     pub name: String,
+    pub names: HashMap<String, String>,
+    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<HashMap<String, f32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Media {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_playing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Bot {
+    pub kind: Option<String>,
+    pub frequency: Option<f32>,
+    pub id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Floor {
+    pub holder: Option<u64>,
+    pub queue: Vec<u64>,
+    pub max_floor_ms: u64,
+    pub remaining_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Whisper {
+    pub from: Option<u64>,
+    #[serde(default)]
+    pub targets: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Breakout {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rooms: Option<Vec<BreakoutRoom>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignments: Option<HashMap<u64, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_random: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_s: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_started: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_s: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BreakoutRoom {
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub participants: Vec<u64>,
+}
+
+/// Hint for the client what the participant or the moderator may want to do.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Suggestion {
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Warning {
+    pub kind: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Echo {
+    pub delay_ms: Option<u64>,
+    pub test_tone: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Level {
+    pub rms_db: f32,
+    pub peak_db: f32,
+    pub is_clipping: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Sound {
+    pub id: String,
+    pub name: String,
+    pub names: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SceneLayer {
+    pub ambience: String,
+    pub volume: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<HashMap<String, f32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Ducking {
+    pub enabled: Option<bool>,
+    pub depth_db: Option<f32>,
+    pub attack_ms: Option<u64>,
+    pub release_ms: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -64,8 +250,7 @@ pub fn room(self_id: u64, name: String, participants: Vec<Participant>, ambience
             message: None,
             history: Some(chat),
         }),
-        is_talking: None,
-        error: None,
+        ..Default::default()
     }
 }
 
@@ -73,85 +258,177 @@ pub fn participants(participants: Vec<Participant>) -> EasymundEvent {
     EasymundEvent {
         event: String::from("participants"),
         participants: Some(participants),
-        room_name: None,
-        ambiences: None,
-        ambience: None,
-        participant: None,
-        chat: None,
-        is_talking: None,
-        error: None,
+        ..Default::default()
     }
 }
 
 pub fn ambience(ambience: String) -> EasymundEvent {
     EasymundEvent {
         event: String::from("ambience"),
-        room_name: None,
-        participants: None,
-        ambiences: None,
         ambience: Some(ambience),
-        participant: None,
-        chat: None,
-        is_talking: None,
-        error: None,
+        ..Default::default()
+    }
+}
+
+pub fn scene(scene: Vec<SceneLayer>) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("scene"),
+        scene: Some(scene),
+        ..Default::default()
+    }
+}
+
+pub fn ducking(ducking: Ducking) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("ducking"),
+        ducking: Some(ducking),
+        ..Default::default()
+    }
+}
+
+pub fn sound(sound: String, participant: Participant) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("sound"),
+        sound: Some(sound),
+        participant: Some(participant),
+        ..Default::default()
+    }
+}
+
+pub fn media(media: Media) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("media"),
+        media: Some(media),
+        ..Default::default()
+    }
+}
+
+pub fn echo(echo: Echo) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("echo"),
+        echo: Some(echo),
+        ..Default::default()
+    }
+}
+
+pub fn level(level: Level) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("level"),
+        level: Some(level),
+        ..Default::default()
+    }
+}
+
+pub fn position(participant: Participant) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("position"),
+        participant: Some(participant),
+        ..Default::default()
+    }
+}
+
+pub fn floor(floor: Floor) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("floor"),
+        floor: Some(floor),
+        ..Default::default()
+    }
+}
+
+pub fn hands(hands: Vec<u64>) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("hands"),
+        hands: Some(hands),
+        ..Default::default()
+    }
+}
+
+pub fn channels(channels: Vec<String>) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("channels"),
+        channels: Some(channels),
+        ..Default::default()
+    }
+}
+
+pub fn channel(channel: Option<String>) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("channel"),
+        channel,
+        ..Default::default()
+    }
+}
+
+pub fn whisper(whisper: Whisper) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("whisper"),
+        whisper: Some(whisper),
+        ..Default::default()
+    }
+}
+
+pub fn breakout(breakout: Breakout) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("breakout"),
+        breakout: Some(breakout),
+        ..Default::default()
+    }
+}
+
+pub fn levels(levels: Vec<(u64, i8, i8)>) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("levels"),
+        levels: Some(levels),
+        ..Default::default()
+    }
+}
+
+pub fn suggestion(suggestion: Suggestion) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("suggestion"),
+        suggestion: Some(suggestion),
+        ..Default::default()
+    }
+}
+
+pub fn warning(warning: Warning) -> EasymundEvent {
+    EasymundEvent {
+        event: String::from("warning"),
+        warning: Some(warning),
+        ..Default::default()
     }
 }
 
 pub fn leave() -> EasymundEvent {
     EasymundEvent {
         event: String::from("leave"),
-        room_name: None,
-        participants: None,
-        ambiences: None,
-        ambience: None,
-        participant: None,
-        chat: None,
-        is_talking: None,
-        error: None,
+        ..Default::default()
     }
 }
 
 pub fn chat_message(chat_message: ChatMessage) -> EasymundEvent {
     EasymundEvent {
         event: String::from("chat"),
-        room_name: None,
-        participants: None,
-        ambiences: None,
-        ambience: None,
-        participant: None,
         chat: Some(Chat {
             message: None,
             history: Some(vec![chat_message]),
         }),
-        is_talking: None,
-        error: None,
+        ..Default::default()
     }
 }
 
 pub fn talking(clients_ids: Vec<u64>) -> EasymundEvent {
     EasymundEvent {
         event: String::from("talking"),
-        room_name: None,
-        participants: None,
-        ambiences: None,
-        ambience: None,
-        participant: None,
-        chat: None,
         is_talking: Some(clients_ids),
-        error: None,
+        ..Default::default()
     }
 }
 
 pub fn error_event(error: String) -> EasymundEvent {
     EasymundEvent {
         event: String::from("error"),
-        room_name: None,
-        participants: None,
-        ambiences: None,
-        ambience: None,
-        participant: None,
-        chat: None,
-        is_talking: None,
         error: Some(error),
+        ..Default::default()
     }
 }
\ No newline at end of file
@@ -1,66 +1,160 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
-use std::fs::File;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use hound::{SampleFormat, WavSpec, WavWriter};
 use log::{debug, error, info};
 use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
 use tokio::{task, time};
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::Mutex;
-use wav::{BitDepth, Header};
 
 use easymund_audio_codec::codec::{Codec, EasymundAudio};
 
-use crate::ambience::Ambience;
+use crate::activity::{Activity, ActivityConfig};
+use crate::ambience::{Ambience, AmbienceConfig, AmbienceDucker, AmbienceLayer, DuckingConfig};
+use crate::bot::VirtualParticipant;
+use crate::breakout::{BreakoutConfig, Breakouts};
 use crate::dto;
+use crate::echo::{EchoConfig, EchoTest};
+use crate::echo_detection::{EchoDetectionConfig, EchoDetector};
 use crate::event_handler::EventHandler;
+use crate::floor::{FloorConfig, FloorControl};
 use crate::httpserver::PostHandler;
+use crate::interpretation::InterpretationConfig;
+use crate::levels;
+use crate::levels::{LevelMeter, LevelsConfig};
+use crate::media;
+use crate::media::{MediaConfig, MediaPlayback};
+use crate::quality::{Issue, QualityConfig, QualityMonitor};
+use crate::routing::RoutingMatrix;
+use crate::session::SessionConfig;
+use crate::soundboard::{OneShot, SoundboardConfig, SoundEffect};
+use crate::spatial;
+use crate::spatial::{Position, SpatialConfig};
+use crate::stage::Stage;
+use crate::vad::{Vad, VadConfig};
 use crate::wsserver::WSClientEvent;
 
-const SAMPLE_RATE: usize = 44100;
-const TALKING_LEVEL: f32 = 0.025;
+pub const SAMPLE_RATE: usize = 44100;
+const VIRTUAL_CLIENT_ID_BASE: u64 = 1 << 62;
+const TIMERS_TICK_MS: u64 = 200;
+const MAX_CREATE_BODY_SIZE: usize = 4096;
+const MAX_UPLOAD_TOKENS: usize = 4;
+
+/// Per-tick state of the room participants used to build individual mixes.
+#[derive(Default)]
+struct Routing {
+    positions: HashMap<u64, Position>,
+    interpreters: HashMap<u64, String>,
+    listen_channels: HashMap<u64, String>,
+    matrix: RoutingMatrix,
+}
 
 pub struct Easymund {
     packet_size: usize,
     context: Context,
 }
 
+/// Sections of the configuration file used by the conference server.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct EasymundConfig {
+    pub ambience: AmbienceConfig,
+    pub soundboard: SoundboardConfig,
+    pub media: MediaConfig,
+    pub echo: EchoConfig,
+    pub spatial: SpatialConfig,
+    pub floor: FloorConfig,
+    pub interpretation: InterpretationConfig,
+    pub breakout: BreakoutConfig,
+    pub session: SessionConfig,
+    pub activity: ActivityConfig,
+    pub levels: LevelsConfig,
+    pub vad: VadConfig,
+    pub quality: QualityConfig,
+    pub echo_detection: EchoDetectionConfig,
+}
+
 pub struct Client {
-    pub room: String,
+    pub room: Option<String>,
     stream: Vec<f32>,
     stream_send_position: usize,
     is_talking: bool,
-    silence_counter: u32,
+    vad: Vad,
+    quality: QualityMonitor,
+    echo_detector: EchoDetector,
     codec: Codec,
     pub participant: Option<Participant>,
+    pub last_sound_time: Option<Instant>,
+    pub bot: Option<Box<dyn VirtualParticipant>>,
+    pub resume_token: Option<String>,
+    pub activity: Activity,
+    pub is_levels_subscribed: bool,
+    level_meter: LevelMeter,
+    disconnected_at: Option<Instant>,
+    output_channels: u8,
+    spatial_gains: HashMap<u64, (f32, f32)>,
 }
 
 impl Client {
-    fn new(room_id: &str, easymun_audio: &EasymundAudio, packet_size: usize) -> Client {
+    fn new(room_id: &str, easymun_audio: &EasymundAudio, packet_size: usize, context: &Context) -> Client {
         Client {
-            room: String::from(room_id),
+            room: Some(String::from(room_id)),
             stream: vec![0.0; packet_size / 2],
             stream_send_position: 0,
             is_talking: false,
-            silence_counter: 0,
+            vad: Vad::new(&context.vad_config, SAMPLE_RATE),
+            quality: QualityMonitor::new(&context.quality_config, SAMPLE_RATE),
+            echo_detector: EchoDetector::new(&context.echo_detection_config, SAMPLE_RATE),
             codec: easymun_audio.create_codec(packet_size).unwrap(),
             participant: None,
+            last_sound_time: None,
+            bot: None,
+            resume_token: None,
+            activity: Activity::new(),
+            is_levels_subscribed: false,
+            level_meter: LevelMeter::default(),
+            disconnected_at: None,
+            output_channels: 1,
+            spatial_gains: HashMap::new(),
         }
     }
+
+    /// Switches the room audio sent to the client to stereo, the microphone stays mono.
+    pub fn set_stereo_output(&mut self) -> Result<(), Box<dyn Error>> {
+        let easymund_audio = EasymundAudio::with_channels(SAMPLE_RATE, 1, 2, 16);
+        self.codec = easymund_audio.create_codec(easymund_audio_codec::default_packet_size())?;
+        self.output_channels = 2;
+        Ok(())
+    }
+
+    /// The connection of the client is open, clients waiting for reconnect get neither audio nor events.
+    pub fn is_online(&self) -> bool {
+        self.disconnected_at.is_none()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Participant {
     pub name: String,
     pub is_admin: bool,
     pub is_muted: bool,
     pub is_sharing: bool,
+    pub can_use_soundboard: bool,
+    pub is_bot: bool,
+    pub position: Option<Position>,
+    pub is_listener: bool,
+    pub interpreter_channel: Option<String>,
+    pub listen_channel: Option<String>,
+    pub is_reconnecting: bool,
+    pub is_deafened: bool,
 }
 
 #[derive(Clone)]
@@ -76,20 +170,197 @@ pub struct Room {
     pub name: String,
     pub clients: HashSet<u64>,
     pub chat: Vec<ChatMessage>,
-    pub ambience_id: String,
-    pub ambience_position: usize,
+    pub scene: Vec<AmbienceLayer>,
+    pub ducker: AmbienceDucker,
+    pub one_shots: Vec<OneShot>,
+    pub media: Option<MediaPlayback>,
+    /// Uploaded media files in the upload order.
+    pub uploads: Vec<(String, Arc<Vec<f32>>)>,
+    /// One-time tokens issued to admins for uploading media files.
+    pub upload_tokens: Vec<String>,
+    pub echo: Option<EchoTest>,
+    pub is_spatial: bool,
+    pub floor: Option<FloorControl>,
+    pub stage: Option<Stage>,
+    pub hands: Vec<u64>,
+    pub channels: Vec<String>,
+    pub routing: RoutingMatrix,
+    pub parent: Option<String>,
+    pub breakouts: Option<Breakouts>,
+    levels_elapsed: usize,
 }
 
 impl Room {
-    fn new(id: String, name: String, ambience_id: &str) -> Room {
+    fn new(id: String, name: String, ambience_id: &str, ducking: &DuckingConfig) -> Room {
         Room {
             id,
             name,
             clients: HashSet::new(),
             chat: Vec::new(),
-            ambience_id: String::from(ambience_id),
-            ambience_position: 0,
+            scene: vec![AmbienceLayer::new(ambience_id, 1.0)],
+            ducker: AmbienceDucker::new(ducking),
+            one_shots: Vec::new(),
+            media: None,
+            uploads: Vec::new(),
+            upload_tokens: Vec::new(),
+            echo: None,
+            is_spatial: false,
+            floor: None,
+            stage: None,
+            hands: Vec::new(),
+            channels: Vec::new(),
+            routing: RoutingMatrix::default(),
+            parent: None,
+            breakouts: None,
+            levels_elapsed: 0,
+        }
+    }
+
+    /// Sub-room of the meeting with the same ambience and room mode.
+    pub fn new_breakout(id: String, name: String, parent: &Room) -> Room {
+        let ambience_id = parent.active_layers().next().map(|layer| layer.playback.ambience_id.as_str()).unwrap_or_default();
+        let mut room = Room::new(id, name, ambience_id, &parent.ducker.config);
+        room.is_spatial = parent.is_spatial;
+        room.parent = Some(parent.id.clone());
+        room
+    }
+
+    /// Private room of a single participant hearing itself, without ambience.
+    fn new_echo(id: String, config: &EchoConfig, ducking: &DuckingConfig) -> Room {
+        let mut room = Room::new(id, String::from("Проверка звука"), "", ducking);
+        room.scene.clear();
+        room.echo = Some(EchoTest::new(config, SAMPLE_RATE));
+        room
+    }
+
+    pub fn echo_state(&self) -> Option<dto::Echo> {
+        self.echo.as_ref().map(|echo| dto::Echo {
+            delay_ms: Some(echo.delay_ms),
+            test_tone: Some(echo.is_tone_enabled()),
+        })
+    }
+
+    /// Replaces the scene layers. Layers of the same ambience keep playing with the new volume,
+    /// missing layers fade out and new layers fade in.
+    pub fn set_scene(&mut self, scene: &[dto::SceneLayer]) {
+        for layer in &mut self.scene {
+            match scene.iter().find(|scene_layer| scene_layer.ambience == layer.playback.ambience_id) {
+                Some(scene_layer) => {
+                    layer.volume = scene_layer.volume;
+                    layer.is_removed = false;
+                    if let Some(params) = &scene_layer.params {
+                        layer.set_params(params);
+                    }
+                }
+                None => layer.is_removed = true,
+            }
         }
+        for scene_layer in scene {
+            if !self.scene.iter().any(|layer| layer.playback.ambience_id == scene_layer.ambience) {
+                let mut layer = AmbienceLayer::new(&scene_layer.ambience, scene_layer.volume);
+                if let Some(params) = &scene_layer.params {
+                    layer.set_params(params);
+                }
+                self.scene.push(layer);
+            }
+        }
+    }
+
+    pub fn play_effect(&mut self, effect_id: &str) {
+        self.one_shots.push(OneShot::new(effect_id));
+    }
+
+    pub fn media_state(&self) -> dto::Media {
+        match &self.media {
+            Some(media) => dto::Media {
+                file: Some(media.file.clone()),
+                position: Some(media.position as f32 / SAMPLE_RATE as f32),
+                duration: Some(media.data.len() as f32 / SAMPLE_RATE as f32),
+                is_playing: Some(media.is_playing),
+                upload_token: None,
+            },
+            None => dto::Media {is_playing: Some(false), ..Default::default()},
+        }
+    }
+
+    pub fn upload(&self, file: &str) -> Option<Arc<Vec<f32>>> {
+        self.uploads.iter().find(|(name, _)| name == file).map(|(_, data)| data.clone())
+    }
+
+    /// Stores the uploaded file replacing the one with the same name, drops the oldest uploads over `max_uploads`.
+    pub fn add_upload(&mut self, file: &str, data: Arc<Vec<f32>>, max_uploads: usize) {
+        self.uploads.retain(|(name, _)| name != file);
+        self.uploads.push((String::from(file), data));
+        let excess = self.uploads.len().saturating_sub(max_uploads);
+        self.uploads.drain(..excess);
+    }
+
+    /// Issues a one-time upload token. Only the latest `MAX_UPLOAD_TOKENS` tokens stay valid.
+    pub fn issue_upload_token(&mut self) -> String {
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        self.upload_tokens.push(token.clone());
+        let excess = self.upload_tokens.len().saturating_sub(MAX_UPLOAD_TOKENS);
+        self.upload_tokens.drain(..excess);
+        token
+    }
+
+    /// Spends the upload token, returns `false` when it was not issued for the room.
+    pub fn take_upload_token(&mut self, token: &str) -> bool {
+        let len = self.upload_tokens.len();
+        self.upload_tokens.retain(|t| t != token);
+        self.upload_tokens.len() < len
+    }
+
+    /// Puts the participant at the end of the raised hands queue. Returns `false` when the hand is already raised.
+    pub fn raise_hand(&mut self, client_id: u64) -> bool {
+        if self.hands.contains(&client_id) {
+            return false;
+        }
+        self.hands.push(client_id);
+        true
+    }
+
+    pub fn lower_hand(&mut self, client_id: u64) -> bool {
+        let length = self.hands.len();
+        self.hands.retain(|id| *id != client_id);
+        self.hands.len() != length
+    }
+
+    /// Position of the participant in the raised hands queue starting from 1.
+    pub fn hand_position(&self, client_id: u64) -> Option<usize> {
+        self.hands.iter().position(|id| *id == client_id).map(|index| index + 1)
+    }
+
+    /// Whether people other than the client are in the room, bots are not counted.
+    pub fn has_other_participants(&self, client_id: u64) -> bool {
+        self.clients.iter().any(|id| *id != client_id && !is_virtual_client(*id))
+    }
+
+    /// Gives the place of the participant in the room to its new connection.
+    pub fn replace_client(&mut self, old_id: u64, new_id: u64) {
+        self.clients.remove(&old_id);
+        self.clients.insert(new_id);
+        if let Some(floor) = &mut self.floor {
+            floor.replace(old_id, new_id);
+        }
+        for id in self.hands.iter_mut().filter(|id| **id == old_id) {
+            *id = new_id;
+        }
+        self.routing.replace(old_id, new_id);
+    }
+
+    /// Removes the participant with its floor, hand and whisper state.
+    pub fn remove_client(&mut self, client_id: u64) {
+        self.clients.remove(&client_id);
+        if let Some(floor) = &mut self.floor {
+            floor.release(client_id);
+        }
+        self.lower_hand(client_id);
+        self.routing.remove(client_id);
+    }
+
+    pub fn active_layers(&self) -> impl Iterator<Item = &AmbienceLayer> {
+        self.scene.iter().filter(|layer| !layer.is_removed)
     }
 }
 
@@ -98,6 +369,212 @@ pub struct Context {
     pub clients: Arc<Mutex<HashMap<u64, Client>>>,
     pub rooms: Arc<Mutex<HashMap<String, Room>>>,
     pub ambiences: Arc<Vec<Ambience>>,
+    pub ambience_config: AmbienceConfig,
+    pub effects: Arc<Vec<SoundEffect>>,
+    pub soundboard_config: SoundboardConfig,
+    pub media_config: MediaConfig,
+    pub echo_config: EchoConfig,
+    pub spatial_config: SpatialConfig,
+    pub floor_config: FloorConfig,
+    pub interpretation_config: InterpretationConfig,
+    pub breakout_config: BreakoutConfig,
+    pub session_config: SessionConfig,
+    pub activity_config: ActivityConfig,
+    pub levels_config: LevelsConfig,
+    pub vad_config: VadConfig,
+    pub quality_config: QualityConfig,
+    pub echo_detection_config: EchoDetectionConfig,
+    pub next_virtual_id: Arc<AtomicU64>,
+}
+
+pub fn is_virtual_client(client_id: u64) -> bool {
+    client_id >= VIRTUAL_CLIENT_ID_BASE
+}
+
+impl Context {
+    /// Adds a virtual participant to the room. It is mixed and listed like any connected client.
+    pub async fn attach_virtual_participant(&self, room_id: &str, bot: Box<dyn VirtualParticipant>) -> Option<u64> {
+        let mut rooms = self.rooms.lock().await;
+        let room = rooms.get_mut(room_id)?;
+        let client_id = self.next_virtual_id.fetch_add(1, Ordering::Relaxed);
+        let easymund_audio = EasymundAudio::new(SAMPLE_RATE, 1, 16);
+        let mut client = Client::new(room_id, &easymund_audio, easymund_audio_codec::default_packet_size(), self);
+        let participant = Participant {
+            name: bot.name(), is_admin: false, is_muted: false, is_sharing: false, can_use_soundboard: false, is_bot: true,
+            position: None, is_listener: false, interpreter_channel: None, listen_channel: None, is_reconnecting: false,
+            is_deafened: false,
+        };
+        info!("Virtual client {} attached to room {}: {:?}", client_id, room_id, &participant);
+        client.participant = Some(participant);
+        client.bot = Some(bot);
+        self.clients.lock().await.insert(client_id, client);
+        room.clients.insert(client_id);
+        Some(client_id)
+    }
+
+    /// Removes the virtual participant, returns the room it was in.
+    pub async fn detach_virtual_participant(&self, client_id: u64) -> Option<String> {
+        if !is_virtual_client(client_id) {
+            return None;
+        }
+        let mut client = self.clients.lock().await.remove(&client_id)?;
+        if let Some(bot) = &mut client.bot {
+            bot.close();
+        }
+        let room_id = client.room?;
+        if let Some(room) = self.rooms.lock().await.get_mut(&room_id) {
+            room.remove_client(client_id);
+        }
+        info!("Virtual client {} detached from room {}", client_id, &room_id);
+        Some(room_id)
+    }
+
+    /// Removes the client from its room keeping the connection, returns the room it has left.
+    /// The admin rights are passed on, bots left alone and a finished sound check room are removed.
+    pub async fn leave_room(&self, client_id: u64) -> Option<String> {
+        let (room_id, is_admin) = {
+            let mut clients = self.clients.lock().await;
+            let client = clients.get_mut(&client_id)?;
+            (client.room.take()?, client.participant.as_ref().is_some_and(|participant| participant.is_admin))
+        };
+        let mut new_admin = None;
+        let mut orphan_bots = Vec::new();
+        let mut is_echo_finished = false;
+        if let Some(room) = self.rooms.lock().await.get_mut(&room_id) {
+            room.remove_client(client_id);
+            if !room.has_other_participants(client_id) {
+                orphan_bots.extend(room.clients.iter().copied());
+                is_echo_finished = room.echo.is_some();
+            }
+            if is_admin {
+                new_admin = room.clients.iter().copied().filter(|id| !is_virtual_client(*id)).min();
+            }
+        }
+        for bot_id in orphan_bots {
+            self.detach_virtual_participant(bot_id).await;
+        }
+        if is_echo_finished {
+            info!("Remove echo room {}", &room_id);
+            self.rooms.lock().await.remove(&room_id);
+        }
+        if let Some(new_admin) = new_admin {
+            if let Some(client) = self.clients.lock().await.get_mut(&new_admin) {
+                if let Some(participant) = &mut client.participant {
+                    participant.is_admin = true;
+                } else {
+                    error!("Unregistered client {} can't be new admin in room {}", new_admin, &room_id);
+                }
+            }
+        }
+        info!("Client {} left room {}", client_id, &room_id);
+        Some(room_id)
+    }
+
+    /// Puts the client without a room into the room. The participant gets the defaults of the room,
+    /// the audio received before entering is not mixed.
+    pub async fn enter_room(&self, client_id: u64, room_id: &str) -> bool {
+        let first_in_room;
+        let is_stage;
+        match self.rooms.lock().await.get_mut(room_id) {
+            Some(room) => {
+                first_in_room = !room.has_other_participants(client_id);
+                is_stage = room.stage.is_some();
+                room.clients.insert(client_id);
+            }
+            None => return false,
+        }
+        if let Some(client) = self.clients.lock().await.get_mut(&client_id) {
+            client.room = Some(String::from(room_id));
+            client.stream_send_position = client.stream.len();
+            client.is_talking = false;
+            client.spatial_gains.clear();
+            if let Some(participant) = &mut client.participant {
+                participant.is_admin = first_in_room;
+                participant.is_listener = is_stage && !first_in_room;
+                participant.position = None;
+                participant.interpreter_channel = None;
+                participant.listen_channel = None;
+            }
+        }
+        info!("Client {} entered room {}", client_id, room_id);
+        true
+    }
+
+    /// Keeps the participant of the dropped connection in the room for the grace period, returns the room.
+    /// Bots, unregistered clients and sound check rooms are not kept.
+    pub async fn suspend_client(&self, client_id: u64) -> Option<String> {
+        if is_virtual_client(client_id) || self.session_config.resume_grace_ms == 0 {
+            return None;
+        }
+        let room_id = self.clients.lock().await.get(&client_id)
+            .filter(|client| client.participant.is_some())
+            .and_then(|client| client.room.clone())?;
+        if self.rooms.lock().await.get(&room_id).is_none_or(|room| room.echo.is_some()) {
+            return None;
+        }
+        let mut clients = self.clients.lock().await;
+        let client = clients.get_mut(&client_id)?;
+        client.disconnected_at = Some(Instant::now());
+        client.is_talking = false;
+        if let Some(participant) = &mut client.participant {
+            participant.is_reconnecting = true;
+        }
+        info!("Client {} is waiting for reconnect in room {}", client_id, &room_id);
+        Some(room_id)
+    }
+
+    /// Clients that have not reconnected within the grace period.
+    pub async fn expired_clients(&self) -> Vec<u64> {
+        let grace_period = self.session_config.grace_period();
+        self.clients.lock().await.iter()
+            .filter(|(_, client)| client.disconnected_at.is_some_and(|disconnected_at| disconnected_at.elapsed() >= grace_period))
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    /// Gives the participant of the dropped connection with the token to the new connection in the same room,
+    /// returns the id of the dropped connection.
+    pub async fn resume_client(&self, client_id: u64, room_id: &str, resume_token: &str) -> Option<u64> {
+        let mut clients = self.clients.lock().await;
+        let old_id = clients.iter()
+            .find(|(_, client)| !client.is_online() && client.room.as_deref() == Some(room_id)
+                && client.resume_token.as_deref() == Some(resume_token))
+            .map(|(old_id, _)| *old_id)?;
+        if !clients.contains_key(&client_id) {
+            return None;
+        }
+        let mut old_client = clients.remove(&old_id)?;
+        if let Some(client) = clients.get_mut(&client_id) {
+            client.participant = old_client.participant.take().map(|participant| Participant {is_reconnecting: false, ..participant});
+            client.resume_token = old_client.resume_token.take();
+        }
+        drop(clients);
+        let mut rooms = self.rooms.lock().await;
+        if let Some(room) = rooms.get_mut(room_id) {
+            room.replace_client(old_id, client_id);
+        }
+        for breakouts in rooms.values_mut().filter_map(|room| room.breakouts.as_mut()) {
+            breakouts.replace_client(old_id, client_id);
+        }
+        info!("Client {} resumed the session of client {} in room {}", client_id, old_id, room_id);
+        Some(old_id)
+    }
+
+    /// Moves the client to another room keeping the connection and the codecs, returns the room it has left.
+    pub async fn move_client(&self, client_id: u64, room_id: &str) -> Option<String> {
+        if !self.rooms.lock().await.contains_key(room_id) {
+            return None;
+        }
+        let is_in_room = self.clients.lock().await.get(&client_id).is_some_and(|client| client.room.as_deref() == Some(room_id));
+        if is_in_room {
+            return None;
+        }
+        let old_room_id = self.leave_room(client_id).await?;
+        if !self.enter_room(client_id, room_id).await {
+            error!("Client {} failed to enter removed room {}", client_id, room_id);
+        }
+        Some(old_room_id)
+    }
 }
 
 struct EasymundPostHandler {
@@ -107,6 +584,12 @@ struct EasymundPostHandler {
 #[derive(Deserialize)]
 struct RoomCreatePostReq {
     name: String,
+    #[serde(default)]
+    is_spatial: bool,
+    #[serde(default)]
+    is_floor_control: bool,
+    #[serde(default)]
+    is_stage: bool,
 }
 #[derive(Serialize)]
 struct RoomCreatePostResp {
@@ -127,17 +610,85 @@ impl PostHandler for EasymundPostHandler {
                     }
                 }
             }
-            _ => None
+            _ => {
+                if let Some((room_id, token, file)) = EasymundPostHandler::parse_upload_path(path) {
+                    match self.handle_upload(room_id, token, file, req_body).await {
+                        Ok(resp) => Some(resp.into_bytes()),
+                        Err(e) => {
+                            error!("Failed to handle {} req: {:?}", path, e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn max_body_size(&self, path: &str) -> usize {
+        if path == "/create" {
+            MAX_CREATE_BODY_SIZE
+        } else if EasymundPostHandler::parse_upload_path(path).is_some() {
+            self.context.media_config.max_upload_mb * 1024 * 1024
+        } else {
+            0
         }
     }
 }
 
+#[derive(Serialize)]
+struct MediaUploadPostResp {
+    file: String,
+    duration: f32,
+}
+
 impl EasymundPostHandler {
+    /// Splits `/upload/<room_id>/<token>/<file>` path.
+    fn parse_upload_path(path: &str) -> Option<(&str, &str, &str)> {
+        let (room_id, path) = path.strip_prefix("/upload/")?.split_once('/')?;
+        let (token, file) = path.split_once('/')?;
+        Some((room_id, token, file))
+    }
+
+    /// Decodes the media file uploaded with a token issued to an admin by the `media_upload` command.
+    /// The body size is limited by `max_upload_mb` before it is read.
+    async fn handle_upload(&self, room_id: &str, token: &str, file: &str, req_body: &[u8]) -> Result<String, Box<dyn Error>> {
+        if !media::is_valid_file_name(file) {
+            return Err(format!("Invalid media file name {}", file))?;
+        }
+        match self.context.rooms.lock().await.get_mut(room_id).map(|room| room.take_upload_token(token)) {
+            Some(true) => {}
+            Some(false) => return Err(format!("Invalid upload token for room {}", room_id))?,
+            None => return Err(format!("Unknown room {}", room_id))?,
+        }
+        let file_name = String::from(file);
+        let data = Vec::from(req_body);
+        let decoded = task::spawn_blocking(move || media::decode_upload(&file_name, data, SAMPLE_RATE)
+            .map_err(|e| e.to_string())).await??;
+        let duration = decoded.len() as f32 / SAMPLE_RATE as f32;
+        info!("Media {} uploaded to room {}, duration {} s", file, room_id, duration);
+        if let Some(room) = self.context.rooms.lock().await.get_mut(room_id) {
+            room.add_upload(file, Arc::new(decoded), self.context.media_config.max_uploads);
+        }
+        let resp = serde_json::to_string(&MediaUploadPostResp {file: String::from(file), duration})?;
+        Ok(resp)
+    }
+
     async fn handle_create(&self, req_body: &[u8]) -> Result<String, Box<dyn Error>> {
         let req: RoomCreatePostReq = serde_json::from_slice(req_body)?;
         let room_id = Alphanumeric.sample_string(&mut rand::thread_rng(), 12);
-        let room = Room::new(room_id.clone(), req.name, &self.context.ambiences[0].id);
-        info!("Create room {} with id {}", &room.name, &room_id);
+        let default_ambience = self.context.ambiences.first().map(|ambience| ambience.id.as_str()).unwrap_or_default();
+        let mut room = Room::new(room_id.clone(), req.name, default_ambience, &self.context.ambience_config.ducking);
+        room.is_spatial = req.is_spatial;
+        if req.is_floor_control {
+            room.floor = Some(FloorControl::new(&self.context.floor_config));
+        }
+        if req.is_stage {
+            room.stage = Some(Stage::new(SAMPLE_RATE, easymund_audio_codec::default_packet_size())?);
+        }
+        info!("Create room {} with id {}, spatial: {}, floor control: {}, stage: {}", &room.name, &room.id, room.is_spatial,
+            room.floor.is_some(), room.stage.is_some());
         self.context.rooms.lock().await.insert(room_id.clone(), room);
         let resp = serde_json::to_string(&RoomCreatePostResp { room_id })?;
         Ok(resp)
@@ -145,17 +696,38 @@ impl EasymundPostHandler {
 }
 
 impl Easymund {
-    pub fn create() -> Self {
-        let ambiences = Ambience::read_dir("sounds").unwrap_or_else(|e| {
+    pub fn create(config: EasymundConfig) -> Self {
+        let ambience_config = config.ambience;
+        let ambiences = Ambience::read_manifest(&ambience_config.sounds_path, SAMPLE_RATE).unwrap_or_else(|e| {
             error!("Failed to read ambiences: {:?}", e);
             Vec::new()
         });
+        let effects = SoundEffect::read_manifest(&ambience_config.sounds_path, SAMPLE_RATE).unwrap_or_else(|e| {
+            error!("Failed to read sound effects: {:?}", e);
+            Vec::new()
+        });
         Self {
             packet_size: easymund_audio_codec::default_packet_size(),
             context: Context {
                 clients: Arc::new(Mutex::new(HashMap::new())),
                 rooms: Arc::new(Mutex::new(HashMap::new())),
-                ambiences: Arc::new(ambiences)
+                ambiences: Arc::new(ambiences),
+                ambience_config,
+                effects: Arc::new(effects),
+                soundboard_config: config.soundboard,
+                media_config: config.media,
+                echo_config: config.echo,
+                spatial_config: config.spatial,
+                floor_config: config.floor,
+                interpretation_config: config.interpretation,
+                breakout_config: config.breakout,
+                session_config: config.session,
+                activity_config: config.activity,
+                levels_config: config.levels,
+                vad_config: config.vad,
+                quality_config: config.quality,
+                echo_detection_config: config.echo_detection,
+                next_virtual_id: Arc::new(AtomicU64::new(VIRTUAL_CLIENT_ID_BASE)),
             }
         }
     }
@@ -180,6 +752,18 @@ impl Easymund {
             }
         });
 
+        let context_clone = self.context.clone();
+        let sender = command_channel.clone();
+        task::spawn(async move {
+            let mut interval = time::interval(Duration::from_millis(TIMERS_TICK_MS));
+            loop {
+                interval.tick().await;
+                EventHandler::handle_breakouts(&context_clone, &sender).await;
+                Easymund::handle_expired_sessions(&context_clone, &sender).await;
+                Easymund::handle_inactive_clients(&context_clone, &sender).await;
+            }
+        });
+
         let context_clone = self.context.clone();
         let sender = command_channel.clone();
         while let Some(event) = events_channel.recv().await {
@@ -201,9 +785,17 @@ impl Easymund {
                                      easymund_audio: &EasymundAudio, packet_size: usize, sender: &Sender<WSClientEvent>) {
         let room_id = path.strip_prefix('/').map(String::from).unwrap_or(path);
         info!("Client {} connect to room {:?}", client_id, &room_id);
+        let room_id = if room_id == context.echo_config.room_id {
+            let echo_room_id = format!("{}-{}", &room_id, client_id);
+            let room = Room::new_echo(echo_room_id.clone(), &context.echo_config, &context.ambience_config.ducking);
+            context.rooms.lock().await.insert(echo_room_id.clone(), room);
+            echo_room_id
+        } else {
+            room_id
+        };
         let room_exists = context.rooms.lock().await.contains_key(&room_id);
         if room_exists {
-            context.clients.lock().await.insert(client_id, Client::new(&room_id, easymund_audio, packet_size));
+            context.clients.lock().await.insert(client_id, Client::new(&room_id, easymund_audio, packet_size, context));
             context.rooms.lock().await.get_mut(room_id.as_str()).unwrap().clients.insert(client_id);
         } else {
             let event = dto::error_event(format!("Конференция {} не существует", &room_id));
@@ -221,38 +813,58 @@ impl Easymund {
 
     async fn handle_client_disconnect(client_id: u64, context: &Context, sender: &Sender<WSClientEvent>) {
         info!("Client {} disconnected", client_id);
-        let mut room_id = None;
-        let mut new_admin = None;
+        if let Some(room_id) = context.suspend_client(client_id).await {
+            EventHandler::handle_participants_update(&room_id, context, sender).await;
+            return;
+        }
+        Easymund::remove_client(client_id, context, sender).await;
+    }
+
+    async fn handle_expired_sessions(context: &Context, sender: &Sender<WSClientEvent>) {
+        for client_id in context.expired_clients().await {
+            info!("Client {} has not reconnected", client_id);
+            Easymund::remove_client(client_id, context, sender).await;
+        }
+    }
+
+    /// Suggests participants who have neither talked nor sent commands for a long time to deafen.
+    async fn handle_inactive_clients(context: &Context, sender: &Sender<WSClientEvent>) {
+        let mut inactive_clients = Vec::new();
+        for (client_id, client) in context.clients.lock().await.iter_mut() {
+            let is_deafened = client.participant.as_ref().is_none_or(|participant| participant.is_deafened);
+            if is_virtual_client(*client_id) || !client.is_online() || is_deafened {
+                continue;
+            }
+            if client.activity.check_deafen_suggestion(&context.activity_config) {
+                inactive_clients.push(*client_id);
+            }
+        }
+        for client_id in inactive_clients {
+            info!("Client {} is inactive, suggest to deafen", client_id);
+            let event = dto::suggestion(dto::Suggestion {kind: String::from("deafen"), participant: Some(client_id), reason: None});
+            let json = serde_json::to_string(&event).unwrap();
+            if let Err(e) = sender.send(WSClientEvent {client_id, is_connected: true, text_message: Some(json), binary_message: None}).await {
+                error!("Failed to send suggestion to client {}: {:?}", client_id, e);
+            }
+        }
+    }
+
+    async fn remove_client(client_id: u64, context: &Context, sender: &Sender<WSClientEvent>) {
+        let room_id = context.leave_room(client_id).await;
         if let Some(client) = context.clients.lock().await.remove(&client_id) {
-            if let Some(room) = context.rooms.lock().await.get_mut(client.room.as_str()) {
-                room.clients.remove(&client_id);
-                room_id = Some(client.room.clone());
-                if let Some(participant) = &client.participant {
-                    if participant.is_admin {
-                        new_admin = room.clients.iter().copied().min();
+            if let (Some(room_id), Some(chime)) = (&room_id, &context.soundboard_config.leave_chime) {
+                if client.participant.is_some() {
+                    if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+                        room.play_effect(chime);
                     }
                 }
             }
 
-            let wav_data = client.stream.iter().map(|f| (f * 32768.0) as i16).collect::<Vec<i16>>();
-            let wav_header = Header::new(1, 1, SAMPLE_RATE as u32, 16);
             let wav_filename = format!("client_{}.wav", client_id);
-            if let Ok(mut file) = File::create(&wav_filename) {
-                if let Err(e) = wav::write(wav_header, &BitDepth::from(wav_data), &mut file) {
-                    error!("Failed to save client stream: {:?}", e);
-                } else {
-                    info!("Client {} stream {} samples written to {}", client_id, client.stream.len(), &wav_filename);
-                }
-            }
-        }
-        
-        if let Some(new_admin) = new_admin {
-            if let Some(client) = context.clients.lock().await.get_mut(&new_admin) {
-                if let Some(participant) = &mut client.participant {
-                    participant.is_admin = true;
-                } else {
-                    error!("Unregistered client {} can't be new admin in room {:?}", new_admin, &room_id);
-                }
+            if let Err(e) = Easymund::write_wav(&wav_filename, &client.stream) {
+                error!("Failed to save client stream: {:?}", e);
+            } else {
+                info!("Client {} stream {} samples written to {}", client_id, client.stream.len(), &wav_filename);
             }
         }
 
@@ -261,6 +873,21 @@ impl Easymund {
         }
     }
 
+    fn write_wav(filename: &str, stream: &[f32]) -> Result<(), Box<dyn Error>> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE as u32,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(filename, spec)?;
+        for v in stream {
+            writer.write_sample((v * 32768.0) as i16)?;
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+
     async fn handle_client_stream(client_id: u64, data: &[u8], context: &Context, sender: &Sender<WSClientEvent>) {
         let first_byte = data[0];
         let data = &data[1..data.len()];
@@ -273,6 +900,9 @@ impl Easymund {
 
     async fn handle_client_audio(client_id: u64, data: &[u8], context: &Context) {
         if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
+            if client.participant.as_ref().is_some_and(|participant| participant.is_listener) {
+                return;
+            }
             match client.codec.decode(data) {
                 Ok(decoded_res) => {
                     let decoded: Vec<Vec<f32>> = decoded_res;
@@ -286,22 +916,25 @@ impl Easymund {
     async fn handle_client_video(client_id: u64, data: &[u8], context: &Context, sender: &Sender<WSClientEvent>) {
         let mut room_id = None;
         if let Some(client) = context.clients.lock().await.get(&client_id) {
-            room_id = Some(client.room.clone());
+            room_id = client.room.clone();
         }
-        let mut send_futures = Vec::new();
+        let mut receivers = Vec::new();
         if let Some(room_id) = room_id {
-            if let Some(room) = context.rooms.lock().await.get_mut(&room_id) {
-                for other_client_id in room.clients.iter().copied() {
-                    if other_client_id != client_id {
-                        let mut frame = Vec::with_capacity(data.len() + 1);
-                        frame.push(1);
-                        frame.extend_from_slice(data);
-                        let event = WSClientEvent {client_id: other_client_id, is_connected: true, text_message: None, binary_message: Some(frame)};
-                        send_futures.push(sender.send(event));
-                    }
-                }
+            if let Some(room) = context.rooms.lock().await.get(&room_id) {
+                receivers.extend(room.clients.iter().copied().filter(|id| *id != client_id));
             }
         }
+        let clients = context.clients.lock().await;
+        receivers.retain(|id| clients.get(id).is_some_and(|client| client.is_online()));
+        drop(clients);
+        let mut send_futures = Vec::new();
+        for other_client_id in receivers {
+            let mut frame = Vec::with_capacity(data.len() + 1);
+            frame.push(1);
+            frame.extend_from_slice(data);
+            let event = WSClientEvent {client_id: other_client_id, is_connected: true, text_message: None, binary_message: Some(frame)};
+            send_futures.push(sender.send(event));
+        }
         for future in send_futures {
             if let Err(e) = future.await {
                 error!("Failed to send video frame: {:?}", e);
@@ -312,44 +945,156 @@ impl Easymund {
     async fn handle_tick(context: Context, sender: &Sender<WSClientEvent>, packet_size: usize) {
         let mut send_futures = Vec::new();
         for room in context.rooms.lock().await.values_mut() {
-            let ambience_chunk = context.ambiences.iter().find(|a| a.id == room.ambience_id)
-                .map(|ambience| Easymund::room_ambience_chunk(room, packet_size, &ambience.data));
-
             let mut talking_clients = Vec::new();
             let mut talking_clients_changes = false;
             let mut clients_chunks = HashMap::new();
+            let mut routing = Routing {matrix: room.routing.clone(), ..Default::default()};
+            let mut listeners = HashMap::new();
+            let mut offline_clients = HashSet::new();
+            let mut deafened_clients = HashSet::new();
+            let mut levels = Vec::new();
+            let mut levels_subscribers = HashSet::new();
+            let mut admins = Vec::new();
+            let mut quality_issues = Vec::new();
+            room.levels_elapsed += packet_size;
+            let is_levels_time = room.levels_elapsed as u64 * 1000 >= context.levels_config.interval_ms * SAMPLE_RATE as u64;
+            if is_levels_time {
+                room.levels_elapsed = 0;
+            }
             for client_id in &room.clients {
                 if let Some(client) = context.clients.lock().await.get_mut(client_id) {
+                    if !client.is_online() {
+                        offline_clients.insert(*client_id);
+                        continue;
+                    }
+                    if client.is_levels_subscribed {
+                        levels_subscribers.insert(*client_id);
+                    }
+                    if let Some(participant) = &client.participant {
+                        if participant.is_admin {
+                            admins.push(*client_id);
+                        }
+                        if let Some(channel) = &participant.listen_channel {
+                            routing.listen_channels.insert(*client_id, channel.clone());
+                        }
+                        if let Some(channel) = &participant.interpreter_channel {
+                            routing.interpreters.insert(*client_id, channel.clone());
+                        }
+                        if let Some(position) = participant.position.filter(|_| room.is_spatial) {
+                            routing.positions.insert(*client_id, position);
+                        }
+                        if participant.is_deafened {
+                            deafened_clients.insert(*client_id);
+                        }
+                        if participant.is_listener {
+                            if !participant.is_deafened {
+                                listeners.insert(*client_id, (client.output_channels, participant.listen_channel.clone()));
+                            }
+                            continue;
+                        }
+                    }
+                    if let Some(chunk) = client.bot.as_mut().and_then(|bot| bot.produce(packet_size)) {
+                        client.stream.extend_from_slice(&chunk);
+                    }
                     let client_chunk_length = client.stream.len() - client.stream_send_position;
                     let client_chunk_length = if client_chunk_length > packet_size {packet_size} else {client_chunk_length};
                     let mut client_chunk = Vec::with_capacity(client_chunk_length);
-                    client_chunk.extend_from_slice(&client.stream[client.stream_send_position .. (client.stream_send_position + client_chunk_length)]);
-                    if Easymund::check_talking_status(client, &client_chunk) {
+                    if room.floor.as_ref().is_none_or(|floor| floor.is_audible(*client_id)) {
+                        client_chunk.extend_from_slice(&client.stream[client.stream_send_position .. (client.stream_send_position + client_chunk_length)]);
+                    }
+                    if Easymund::check_talking_status(client, &client_chunk, packet_size) {
                         talking_clients_changes = true;
                     }
                     if client.is_talking {
+                        client.activity.touch();
+                    }
+                    if !is_virtual_client(*client_id) {
+                        let noise_floor_db = client.vad.noise_floor_db();
+                        let issues = client.quality.analyze(&client_chunk, packet_size, client.is_talking, noise_floor_db,
+                                                            &context.quality_config);
+                        quality_issues.extend(issues.into_iter().map(|issue| (*client_id, issue)));
+                        match client.echo_detector.process(&client_chunk, packet_size) {
+                            Some(true) => quality_issues.push((*client_id, Issue::Echo)),
+                            Some(false) => info!("Echo of client {} is over", client_id),
+                            None => {}
+                        }
+                    }
+                    client.level_meter.measure(&client_chunk);
+                    if is_levels_time {
+                        levels.push(levels::compact(*client_id, &client.level_meter.take()));
+                    }
+                    if client.is_talking && !room.routing.is_whispering(*client_id) {
                         talking_clients.push(*client_id);
                     }
+                    client.echo_detector.attenuate(&mut client_chunk);
                     client.stream_send_position += client_chunk_length;
                     clients_chunks.insert(*client_id, client_chunk);
                 }
             }
-            let talking_clients_event_json = if talking_clients_changes {
-                match serde_json::to_string(&dto::talking(talking_clients)) {
-                    Ok(json) => Some(json),
-                    Err(e) => {
-                        error!("Failed to serialize talking event: {:?}", e);
-                        None
-                    }
+            let (echo_chunk, level_event) = Easymund::room_echo_chunk(room, &clients_chunks, packet_size);
+            let (media_chunk, media_event) = Easymund::room_media_chunk(room, packet_size);
+            let is_media_playing = room.media.as_ref().is_some_and(|media| media.is_playing);
+            let mut ambience_chunk = Easymund::room_ambience_chunk(room, packet_size, &context);
+            room.ducker.apply(&mut ambience_chunk, !talking_clients.is_empty() || is_media_playing, SAMPLE_RATE);
+            let effects_chunk = Easymund::room_effects_chunk(room, packet_size, &context);
+            let ambience_chunk = Some(Easymund::mix(&[ambience_chunk.as_slice(), effects_chunk.as_slice(), media_chunk.as_slice(),
+                echo_chunk.as_slice()]));
+
+            let mut room_events = Vec::new();
+            if talking_clients_changes {
+                room_events.push(dto::talking(talking_clients));
+            }
+            if let Some(event) = media_event {
+                room_events.push(event);
+            }
+            if let Some(event) = level_event {
+                room_events.push(event);
+            }
+            if let Some(floor) = &mut room.floor {
+                if floor.check_timeout() {
+                    info!("Floor time is over in room {}, floor is passed to {:?}", &room.id, floor.holder);
+                    room_events.push(dto::floor(floor.state()));
+                }
+            }
+            let audience_frames = Easymund::create_audience_frames(room, &ambience_chunk, &clients_chunks, &listeners, &routing, &context);
+            let levels_json = if levels_subscribers.is_empty() || levels.is_empty() {
+                None
+            } else {
+                levels.sort_by_key(|(client_id, _, _)| *client_id);
+                serde_json::to_string(&dto::levels(levels)).ok()
+            };
+            let room_events_json: Vec<String> = room_events.iter().filter_map(|event| match serde_json::to_string(event) {
+                Ok(json) => Some(json),
+                Err(e) => {
+                    error!("Failed to serialize {} event: {:?}", &event.event, e);
+                    None
                 }
-            } else { None };
+            }).collect();
+            for event in Easymund::quality_events(&quality_issues, &admins) {
+                send_futures.push(sender.send(event));
+            }
 
             for client_id in &room.clients {
-                if let Some(event) = Easymund::create_client_audio_event(*client_id, &ambience_chunk, &clients_chunks, &context).await {
+                if offline_clients.contains(client_id) {
+                    continue;
+                }
+                if !deafened_clients.contains(client_id) {
+                    if let Some(frame) = listeners.get(client_id).and_then(|listener| audience_frames.get(listener)) {
+                        let event = WSClientEvent { client_id: *client_id, is_connected: true, text_message: None, binary_message: Some(frame.clone()) };
+                        send_futures.push(sender.send(event));
+                    } else if let Some(event) = Easymund::create_client_audio_event(*client_id, &ambience_chunk, &clients_chunks, &routing, &context).await {
+                        send_futures.push(sender.send(event));
+                    }
+                }
+                if is_virtual_client(*client_id) {
+                    continue;
+                }
+                if let Some(json) = levels_json.as_ref().filter(|_| levels_subscribers.contains(client_id)) {
+                    let event = WSClientEvent { client_id: *client_id, is_connected: true, text_message: Some(json.clone()), binary_message: None };
                     send_futures.push(sender.send(event));
                 }
-                if let Some(json) = talking_clients_event_json.clone() {
-                    let event = WSClientEvent { client_id: *client_id, is_connected: true, text_message: Some(json), binary_message: None };
+                for json in &room_events_json {
+                    let event = WSClientEvent { client_id: *client_id, is_connected: true, text_message: Some(json.clone()), binary_message: None };
                     send_futures.push(sender.send(event));
                 }
             }
@@ -361,82 +1106,253 @@ impl Easymund {
         }
     }
     
-    async fn create_client_audio_event(client_id: u64, ambience_chunk: &Option<Vec<f32>>,
-                                  clients_chunks: &HashMap<u64, Vec<f32>>, context: &Context) -> Option<WSClientEvent> {
+    /// Encodes the mix of the whole stage once for every channels count and interpretation channel
+    /// the audience listens to.
+    fn create_audience_frames(room: &mut Room, ambience_chunk: &Option<Vec<f32>>, clients_chunks: &HashMap<u64, Vec<f32>>,
+                              listeners: &HashMap<u64, (u8, Option<String>)>, routing: &Routing, context: &Context)
+                              -> HashMap<(u8, Option<String>), Vec<u8>> {
+        let mut frames = HashMap::new();
+        let stage = match &mut room.stage {
+            Some(stage) if !listeners.is_empty() => stage,
+            _ => return frames,
+        };
+        let mut channels: Vec<&[f32]> = clients_chunks.iter()
+            .filter(|(client_id, _)| !routing.interpreters.contains_key(client_id) && !routing.matrix.is_whispering(**client_id))
+            .map(|(_, chunk)| chunk.as_slice())
+            .collect();
+        if let Some(chunk) = ambience_chunk {
+            channels.push(chunk);
+        }
+        let floor_chunk = Easymund::mix(&channels);
+        for listener in listeners.values() {
+            if frames.contains_key(listener) {
+                continue;
+            }
+            let (channels_count, listen_channel) = listener;
+            let chunk = match listen_channel {
+                Some(channel) => {
+                    let interpretation = Easymund::interpretation_chunk(channel, clients_chunks, routing);
+                    Easymund::duck_floor(&floor_chunk, &interpretation, context)
+                }
+                None => floor_chunk.clone(),
+            };
+            if chunk.is_empty() {
+                continue;
+            }
+            match stage.encode(&chunk, *channels_count) {
+                Ok(bytes) => { frames.insert(listener.clone(), Easymund::audio_frame(&bytes)); }
+                Err(e) => error!("Failed to encode audience mix: {:?}", e),
+            }
+        }
+        frames
+    }
+
+    fn interpretation_chunk(channel: &str, clients_chunks: &HashMap<u64, Vec<f32>>, routing: &Routing) -> Vec<f32> {
+        let channels: Vec<&[f32]> = clients_chunks.iter()
+            .filter(|(client_id, _)| routing.interpreters.get(client_id).is_some_and(|interpreter_channel| interpreter_channel == channel))
+            .map(|(_, chunk)| chunk.as_slice())
+            .collect();
+        Easymund::mix(&channels)
+    }
+
+    /// Floor audio lowered under the interpretation.
+    fn duck_floor(floor_chunk: &[f32], interpretation_chunk: &[f32], context: &Context) -> Vec<f32> {
+        let floor_gain = context.interpretation_config.floor_gain();
+        let floor_chunk: Vec<f32> = floor_chunk.iter().map(|v| v * floor_gain).collect();
+        Easymund::mix(&[floor_chunk.as_slice(), interpretation_chunk])
+    }
+
+    fn audio_frame(bytes: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(bytes.len() + 1);
+        frame.push(0);
+        frame.extend_from_slice(bytes);
+        frame
+    }
+
+    async fn create_client_audio_event(client_id: u64, ambience_chunk: &Option<Vec<f32>>, clients_chunks: &HashMap<u64, Vec<f32>>,
+                                       routing: &Routing, context: &Context) -> Option<WSClientEvent> {
         let mut channels = Vec::new();
         if let Some(chunk) = ambience_chunk {
             if !chunk.is_empty() {
                 channels.push(chunk.as_slice());
             }
         }
+        let listener = routing.positions.get(&client_id);
+        let listen_channel = routing.listen_channels.get(&client_id);
+        let mut spatial_sources = Vec::new();
         for (other_client_id, other_client_chunk) in clients_chunks {
-            if client_id != *other_client_id {
-                channels.push(other_client_chunk);
-            }
-        }
-        let chunk = Easymund::mix(&channels);
-        if !chunk.is_empty() {
-            let mut encoded: Option<Vec<u8>> = None;
-            if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
-                match client.codec.encode(vec![chunk.as_slice()].as_slice()) {
-                    Ok(encoded_res) => { encoded = Some(encoded_res); }
-                    Err(e) => { error!("Failed to encode: {:?}", e); }
+            if client_id != *other_client_id && !routing.interpreters.contains_key(other_client_id)
+                && routing.matrix.can_hear(client_id, *other_client_id) {
+                match (listener, routing.positions.get(other_client_id)) {
+                    (Some(listener), Some(source)) => spatial_sources.push((*other_client_id, other_client_chunk, listener, source)),
+                    _ => channels.push(other_client_chunk),
                 }
             }
+        }
+        let mut chunk = Easymund::mix(&channels);
+        let interpretation = listen_channel
+            .map(|channel| Easymund::interpretation_chunk(channel, clients_chunks, routing))
+            .unwrap_or_default();
 
-            if let Some(bytes) = encoded {
-                let mut frame = Vec::with_capacity(bytes.len() + 1);
-                frame.push(0);
-                frame.extend_from_slice(&bytes);
-                Some(WSClientEvent { client_id, is_connected: true, text_message: None, binary_message: Some(frame) })
-            } else { None }
-        } else { None }
-    }
-    
-    fn check_talking_status(client: &mut Client, chunk: &[f32]) -> bool {
-        let average_level = chunk.iter()
-            .map(|v| v.abs())
-            .reduce(|acc, v| acc + v).unwrap_or_default() / (chunk.len() as f32 + 1.0);
-        if average_level > TALKING_LEVEL {
-            if !client.is_talking {
-                client.is_talking = true;
-                client.silence_counter = 0;
-                true
-            } else {
-                false
+        let mut clients = context.clients.lock().await;
+        let client = clients.get_mut(&client_id)?;
+        if let Some(bot) = &mut client.bot {
+            chunk.resize(easymund_audio_codec::default_packet_size(), 0.0);
+            bot.consume(&chunk);
+            return None;
+        }
+        if chunk.is_empty() && spatial_sources.is_empty() && listen_channel.is_none() {
+            return None;
+        }
+        let is_stereo = client.output_channels == 2;
+        let mut right = Vec::new();
+        if !spatial_sources.is_empty() {
+            chunk.resize(easymund_audio_codec::default_packet_size(), 0.0);
+            right = chunk.clone();
+            let mut gains = HashMap::with_capacity(spatial_sources.len());
+            for (source_id, source_chunk, listener, source) in spatial_sources {
+                let (left_gain, right_gain) = if is_stereo {
+                    context.spatial_config.stereo_gains(listener, source)
+                } else {
+                    let gain = context.spatial_config.gain(listener, source);
+                    (gain, gain)
+                };
+                let (prev_left_gain, prev_right_gain) = client.spatial_gains.get(&source_id).copied()
+                    .unwrap_or((left_gain, right_gain));
+                spatial::mix_ramped(&mut chunk, source_chunk, prev_left_gain, left_gain);
+                spatial::mix_ramped(&mut right, source_chunk, prev_right_gain, right_gain);
+                gains.insert(source_id, (left_gain, right_gain));
             }
-        } else if client.is_talking {
-            client.silence_counter += 1;
-            if client.silence_counter >= 5 {
-                client.is_talking = false;
-                true
-            } else {
-                false
+            client.spatial_gains = gains;
+        }
+        if listen_channel.is_some() {
+            chunk = Easymund::duck_floor(&chunk, &interpretation, context);
+            if !right.is_empty() {
+                right = Easymund::duck_floor(&right, &interpretation, context);
+            }
+            if chunk.is_empty() {
+                return None;
             }
+        }
+        if right.is_empty() {
+            client.echo_detector.sent(&chunk);
+        } else {
+            // Both speakers reach the microphone
+            let downmix: Vec<f32> = chunk.iter().zip(&right).map(|(l, r)| (l + r) / 2.0).collect();
+            client.echo_detector.sent(&downmix);
+        }
+        let encode_result = if !is_stereo {
+            client.codec.encode(&[chunk.as_slice()])
+        } else if right.is_empty() {
+            client.codec.encode(&[chunk.as_slice(), chunk.as_slice()])
         } else {
-            false
+            client.codec.encode(&[chunk.as_slice(), right.as_slice()])
+        };
+        match encode_result {
+            Ok(bytes) => {
+                Some(WSClientEvent { client_id, is_connected: true, text_message: None, binary_message: Some(Easymund::audio_frame(&bytes)) })
+            }
+            Err(e) => {
+                error!("Failed to encode: {:?}", e);
+                None
+            }
         }
     }
 
-    fn room_ambience_chunk(room: &mut Room, samples_count: usize, background: &[f32]) -> Vec<f32> {
-        if background.len() < samples_count {
-            return Vec::new();
+    /// Private warnings for the participants with audio issues and suggestions to mute them for the moderators.
+    fn quality_events(issues: &[(u64, Issue)], admins: &[u64]) -> Vec<WSClientEvent> {
+        let mut events = Vec::new();
+        for (client_id, issue) in issues {
+            info!("Client {} has audio issue: {}", client_id, issue.kind());
+            let mut recipients = vec![(*client_id, dto::warning(dto::Warning {kind: String::from(issue.kind())}))];
+            for admin_id in admins.iter().filter(|admin_id| *admin_id != client_id) {
+                let suggestion = dto::Suggestion {kind: String::from("mute"), participant: Some(*client_id),
+                    reason: Some(String::from(issue.kind()))};
+                recipients.push((*admin_id, dto::suggestion(suggestion)));
+            }
+            for (recipient_id, event) in recipients {
+                let json = serde_json::to_string(&event).unwrap();
+                events.push(WSClientEvent {client_id: recipient_id, is_connected: true, text_message: Some(json), binary_message: None});
+            }
         }
-        let mut chunk = Vec::with_capacity(samples_count);
-        let mut pos = room.ambience_position;
-        for _ in 0..samples_count {
-            pos = if pos >= background.len() - 1 {0} else {pos + 1};
-            chunk.push(background[pos]);
+        events
+    }
+
+    fn check_talking_status(client: &mut Client, chunk: &[f32], samples_count: usize) -> bool {
+        let is_talking = client.vad.process(chunk, samples_count);
+        let is_changed = is_talking != client.is_talking;
+        client.is_talking = is_talking;
+        is_changed
+    }
+
+    fn room_ambience_chunk(room: &mut Room, samples_count: usize, context: &Context) -> Vec<f32> {
+        let loop_crossfade = context.ambience_config.loop_crossfade(SAMPLE_RATE);
+        let switch_crossfade = context.ambience_config.switch_crossfade(SAMPLE_RATE);
+        let mut layers_chunks = Vec::with_capacity(room.scene.len());
+        room.scene.retain(|layer| !layer.is_finished()
+            && context.ambiences.iter().any(|ambience| ambience.id == layer.playback.ambience_id));
+        for layer in &mut room.scene {
+            if let Some(ambience) = context.ambiences.iter().find(|ambience| ambience.id == layer.playback.ambience_id) {
+                let chunk = layer.next_chunk(ambience, samples_count, loop_crossfade, switch_crossfade);
+                if !chunk.is_empty() {
+                    layers_chunks.push(chunk);
+                }
+            }
         }
-        room.ambience_position = pos;
-        chunk
+        let channels: Vec<&[f32]> = layers_chunks.iter().map(|chunk| chunk.as_slice()).collect();
+        Easymund::mix(&channels)
     }
 
-    fn mix(channels: &[&[f32]]) -> Vec<f32> {
-        if channels.is_empty() {
-            return Vec::new()
+    /// Renders the delayed audio of the echo room participant with the level event once per level interval.
+    fn room_echo_chunk(room: &mut Room, clients_chunks: &HashMap<u64, Vec<f32>>, samples_count: usize)
+                       -> (Vec<f32>, Option<dto::EasymundEvent>) {
+        match &mut room.echo {
+            Some(echo) => {
+                let input = clients_chunks.iter()
+                    .find(|(client_id, _)| !is_virtual_client(**client_id))
+                    .map(|(_, chunk)| chunk.as_slice())
+                    .unwrap_or_default();
+                let chunk = echo.next_chunk(input, samples_count);
+                (chunk, echo.take_level().map(dto::level))
+            }
+            None => (Vec::new(), None),
         }
-        let length = channels[0].len();
+    }
+
+    /// Renders the next chunk of the room media. The media state event is created once a second while playing
+    /// and when the playback is over.
+    fn room_media_chunk(room: &mut Room, samples_count: usize) -> (Vec<f32>, Option<dto::EasymundEvent>) {
+        if let Some(media) = &mut room.media {
+            let was_playing = media.is_playing;
+            let second = media.position / SAMPLE_RATE;
+            let chunk = media.next_chunk(samples_count);
+            if was_playing && (!media.is_playing || media.position / SAMPLE_RATE != second) {
+                return (chunk, Some(dto::media(room.media_state())));
+            }
+            return (chunk, None);
+        }
+        (Vec::new(), None)
+    }
+
+    fn room_effects_chunk(room: &mut Room, samples_count: usize, context: &Context) -> Vec<f32> {
+        let mut effects_chunks = Vec::with_capacity(room.one_shots.len());
+        room.one_shots.retain_mut(|one_shot| {
+            let chunk = context.effects.iter().find(|effect| effect.id == one_shot.effect_id)
+                .and_then(|effect| one_shot.next_chunk(effect, samples_count));
+            match chunk {
+                Some(chunk) => {
+                    effects_chunks.push(chunk);
+                    true
+                }
+                None => false,
+            }
+        });
+        let channels: Vec<&[f32]> = effects_chunks.iter().map(|chunk| chunk.as_slice()).collect();
+        Easymund::mix(&channels)
+    }
+
+    fn mix(channels: &[&[f32]]) -> Vec<f32> {
+        let length = channels.iter().map(|channel| channel.len()).max().unwrap_or_default();
         let mut result = Vec::with_capacity(length);
         for i in 0..length {
             let mut v = 0.0;
@@ -450,3 +1366,47 @@ impl Easymund {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    async fn send_event(client_id: u64, json: &str, context: &Context, sender: &Sender<WSClientEvent>) {
+        EventHandler::handle_client_event(client_id, String::from(json), context, sender).await;
+    }
+
+    async fn participant(client_id: u64, context: &Context) -> Participant {
+        let clients = context.clients.lock().await;
+        clients.get(&client_id).and_then(|client| client.participant.clone()).expect("Participant must exist")
+    }
+
+    #[tokio::test]
+    async fn test_join_room_keeps_audio_settings() {
+        let easymund = Easymund::create(EasymundConfig::default());
+        let context = &easymund.context;
+        let ducking = DuckingConfig::default();
+        for room_id in ["first", "second"] {
+            let room = Room::new(String::from(room_id), String::from(room_id), "", &ducking);
+            context.rooms.lock().await.insert(String::from(room_id), room);
+        }
+        let (sender, _receiver) = mpsc::channel(1024);
+        let audio = EasymundAudio::new(SAMPLE_RATE, 1, 16);
+        for client_id in [1, 2] {
+            Easymund::handle_client_connected(client_id, String::from("/first"), context, &audio, easymund.packet_size, &sender).await;
+        }
+        send_event(1, r#"{"event": "join", "participant": {"name": "Anna"}}"#, context, &sender).await;
+        send_event(2, r#"{"event": "join", "participant": {"name": "Boris"}}"#, context, &sender).await;
+        send_event(2, r#"{"event": "participant", "participant": {"is_muted": false, "is_deafened": true}}"#, context, &sender).await;
+        assert!(!participant(2, context).await.is_admin);
+
+        send_event(2, r#"{"event": "join_room", "room_id": "second"}"#, context, &sender).await;
+        let participant = participant(2, context).await;
+        assert_eq!(participant.name, "Boris");
+        assert!(!participant.is_muted);
+        assert!(participant.is_deafened);
+        assert!(participant.is_admin, "The first participant of the room becomes its admin");
+        assert_eq!(context.clients.lock().await.get(&2).and_then(|client| client.room.clone()).as_deref(), Some("second"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+
+use crate::bot::{TestToneBot, VirtualParticipant};
+use crate::dto;
+use crate::levels::LevelMeter;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EchoConfig {
+    pub room_id: String,
+    pub delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub tone_frequency: f32,
+    pub tone_level: f32,
+    pub level_interval_ms: u64,
+}
+
+impl Default for EchoConfig {
+    fn default() -> Self {
+        Self {
+            room_id: String::from("echo"),
+            delay_ms: 1500,
+            max_delay_ms: 5000,
+            tone_frequency: 440.0,
+            tone_level: 0.1,
+            level_interval_ms: 250,
+        }
+    }
+}
+
+/// Microphone and speaker check: plays the participant's own audio back after a delay and measures its level.
+pub struct EchoTest {
+    pub delay_ms: u64,
+    delay_line: VecDeque<f32>,
+    tone: Option<TestToneBot>,
+    sample_rate: usize,
+    level_interval: usize,
+    elapsed: usize,
+    meter: LevelMeter,
+}
+
+impl EchoTest {
+    pub fn new(config: &EchoConfig, sample_rate: usize) -> Self {
+        let mut echo = Self {
+            delay_ms: 0,
+            delay_line: VecDeque::new(),
+            tone: None,
+            sample_rate,
+            level_interval: config.level_interval_ms as usize * sample_rate / 1000,
+            elapsed: 0,
+            meter: LevelMeter::default(),
+        };
+        echo.set_delay(config.delay_ms.min(config.max_delay_ms));
+        echo
+    }
+
+    /// Restarts the delay line with the new delay.
+    pub fn set_delay(&mut self, delay_ms: u64) {
+        self.delay_ms = delay_ms;
+        self.delay_line.clear();
+        self.delay_line.resize(delay_ms as usize * self.sample_rate / 1000, 0.0);
+    }
+
+    pub fn set_tone(&mut self, config: &EchoConfig, is_enabled: bool) {
+        self.tone = if is_enabled {
+            Some(TestToneBot::new(config.tone_frequency, config.tone_level, self.sample_rate))
+        } else {
+            None
+        };
+    }
+
+    pub fn is_tone_enabled(&self) -> bool {
+        self.tone.is_some()
+    }
+
+    /// Measures the participant's chunk and returns the delayed audio with the test tone. Missing input is
+    /// replaced by silence to keep the delay constant.
+    pub fn next_chunk(&mut self, input: &[f32], samples_count: usize) -> Vec<f32> {
+        self.meter.measure(input);
+        self.elapsed += samples_count;
+
+        let input_length = input.len().min(samples_count);
+        self.delay_line.extend(&input[..input_length]);
+        self.delay_line.resize(self.delay_line.len() + samples_count - input_length, 0.0);
+        let mut chunk: Vec<f32> = self.delay_line.drain(..samples_count).collect();
+        if let Some(tone) = self.tone.as_mut().and_then(|tone| tone.produce(samples_count)) {
+            chunk.iter_mut().zip(tone).for_each(|(v, t)| *v += t);
+        }
+        chunk
+    }
+
+    /// Returns the input level once per level interval and starts a new measurement.
+    pub fn take_level(&mut self) -> Option<dto::Level> {
+        if self.elapsed < self.level_interval {
+            return None;
+        }
+        self.elapsed = 0;
+        Some(self.meter.take())
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use log::debug;
+use serde::Deserialize;
+
+use easymund_audio_codec::fft::{Complex, Fft};
+
+use crate::levels;
+
+/// Both signals are averaged over blocks of this many samples before the correlation, which keeps the band
+/// below 1 kHz where the most of the speech energy is and makes the delay search cheap.
+const DECIMATION: usize = 20;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EchoDetectionConfig {
+    /// How often the participant's audio is correlated with the mix sent to them, 0 disables the detection.
+    pub interval_ms: u64,
+    /// Length of the participant's audio correlated at once.
+    pub window_ms: u64,
+    /// Longest time the mix takes to come back from the participant's speakers.
+    pub max_delay_ms: u64,
+    /// Normalized correlation taken for echo.
+    pub correlation: f32,
+    /// Both the participant's audio and the mix have to be louder than this.
+    pub min_level_db: f32,
+    /// Time the echo is kept after it was last found.
+    pub hold_ms: u64,
+    /// Attenuation of the participant's audio in the mix while the echo lasts, 0 disables it.
+    pub attenuation_db: f32,
+}
+
+impl Default for EchoDetectionConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 500,
+            window_ms: 1000,
+            max_delay_ms: 800,
+            correlation: 0.4,
+            min_level_db: -50.0,
+            hold_ms: 2000,
+            attenuation_db: 0.0,
+        }
+    }
+}
+
+/// Averages blocks of samples, the remainder of a chunk is kept for the next one.
+#[derive(Default)]
+struct Decimator {
+    sum: f32,
+    count: usize,
+}
+
+impl Decimator {
+    fn push(&mut self, chunk: &[f32], samples_count: usize, output: &mut VecDeque<f32>) {
+        for i in 0..samples_count {
+            self.sum += chunk.get(i).copied().unwrap_or(0.0);
+            self.count += 1;
+            if self.count == DECIMATION {
+                output.push_back(self.sum / DECIMATION as f32);
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+}
+
+/// Finds the mix sent to the participant in the audio received from them. The participant's audio is
+/// correlated with the mix delayed by up to `max_delay_ms`, a high correlation means their microphone
+/// picks up the speakers. The correlation for all the delays is computed at once in the frequency domain.
+pub struct EchoDetector {
+    config: EchoDetectionConfig,
+    fft: Fft,
+    interval_samples: usize,
+    window_length: usize,
+    max_lag: usize,
+    hold_samples: usize,
+    near_decimator: Decimator,
+    far_decimator: Decimator,
+    near: VecDeque<f32>,
+    far: VecDeque<f32>,
+    sent_chunk: Vec<f32>,
+    elapsed: usize,
+    since_echo: usize,
+    is_echo: bool,
+    gain: f32,
+}
+
+impl EchoDetector {
+    pub fn new(config: &EchoDetectionConfig, sample_rate: usize) -> Self {
+        let hold_samples = config.hold_ms as usize * sample_rate / 1000;
+        let window_length = config.window_ms as usize * sample_rate / 1000 / DECIMATION;
+        let max_lag = config.max_delay_ms as usize * sample_rate / 1000 / DECIMATION;
+        Self {
+            config: config.clone(),
+            // Long enough for the mix and the window not to wrap around
+            fft: Fft::new((2 * window_length + max_lag).max(2).next_power_of_two()),
+            interval_samples: config.interval_ms as usize * sample_rate / 1000,
+            window_length,
+            max_lag,
+            hold_samples,
+            near_decimator: Decimator::default(),
+            far_decimator: Decimator::default(),
+            near: VecDeque::new(),
+            far: VecDeque::new(),
+            sent_chunk: Vec::new(),
+            elapsed: 0,
+            since_echo: hold_samples,
+            is_echo: false,
+            gain: 1.0,
+        }
+    }
+
+    /// Remembers the mix sent to the participant in this tick.
+    pub fn sent(&mut self, chunk: &[f32]) {
+        self.sent_chunk.clear();
+        self.sent_chunk.extend_from_slice(chunk);
+    }
+
+    /// Adds the participant's chunk of the tick and the mix sent in the previous tick, a tick without
+    /// the mix is taken for silence. Returns the new state when the echo starts or ends.
+    pub fn process(&mut self, chunk: &[f32], samples_count: usize) -> Option<bool> {
+        if self.interval_samples == 0 {
+            return None;
+        }
+        self.near_decimator.push(chunk, samples_count, &mut self.near);
+        self.far_decimator.push(&self.sent_chunk, samples_count, &mut self.far);
+        self.sent_chunk.clear();
+        while self.near.len() > self.window_length {
+            self.near.pop_front();
+        }
+        while self.far.len() > self.window_length + self.max_lag {
+            self.far.pop_front();
+        }
+
+        self.elapsed += samples_count;
+        self.since_echo += samples_count;
+        if self.elapsed < self.interval_samples || self.near.len() < self.window_length {
+            return None;
+        }
+        self.elapsed = 0;
+        let (correlation, lag) = self.max_correlation();
+        if correlation >= self.config.correlation {
+            debug!("Echo correlation {} with delay {} samples", correlation, lag * DECIMATION);
+            self.since_echo = 0;
+        }
+        let is_echo = self.since_echo < self.hold_samples;
+        if is_echo == self.is_echo {
+            return None;
+        }
+        self.is_echo = is_echo;
+        Some(is_echo)
+    }
+
+    /// Lowers the participant's chunk by `attenuation_db` while the echo lasts, the gain is ramped over
+    /// the chunk to avoid clicks.
+    pub fn attenuate(&mut self, chunk: &mut [f32]) {
+        let target_gain = if self.is_echo { 10_f32.powf(-self.config.attenuation_db / 20.0) } else { 1.0 };
+        if self.gain == target_gain && target_gain == 1.0 {
+            return;
+        }
+        let step = (target_gain - self.gain) / chunk.len().max(1) as f32;
+        for v in chunk.iter_mut() {
+            self.gain += step;
+            *v *= self.gain;
+        }
+        self.gain = target_gain;
+    }
+
+    /// Highest normalized correlation of the participant's window with the mix delayed by up to `max_lag`
+    /// and the delay it is found at, in decimated samples.
+    fn max_correlation(&self) -> (f32, usize) {
+        let near_length = self.near.len();
+        let far_length = self.far.len();
+        let near_energy: f32 = self.near.iter().map(|v| v * v).sum();
+        if !self.is_audible(near_energy, near_length) || far_length < near_length {
+            return (0.0, 0);
+        }
+        let mut far_spectrum: Vec<Complex> = self.far.iter().map(|v| Complex::new(*v, 0.0)).collect();
+        far_spectrum.resize(self.fft.size(), Complex::default());
+        self.fft.forward(&mut far_spectrum);
+        let mut near_spectrum: Vec<Complex> = self.near.iter().map(|v| Complex::new(*v, 0.0)).collect();
+        near_spectrum.resize(self.fft.size(), Complex::default());
+        self.fft.forward(&mut near_spectrum);
+        // Products of the window with the mix starting at every offset
+        let mut products: Vec<Complex> = far_spectrum.iter().zip(&near_spectrum).map(|(f, n)| *f * n.conj()).collect();
+        self.fft.inverse(&mut products);
+
+        let mut far_energies = Vec::with_capacity(far_length + 1);
+        far_energies.push(0.0_f64);
+        for v in &self.far {
+            far_energies.push(far_energies[far_energies.len() - 1] + (*v as f64) * (*v as f64));
+        }
+        let mut max_correlation = (0.0, 0);
+        for start in 0..=(far_length - near_length) {
+            let far_energy = (far_energies[start + near_length] - far_energies[start]).max(0.0) as f32;
+            if !self.is_audible(far_energy, near_length) {
+                continue;
+            }
+            let correlation = products[start].re / (near_energy * far_energy).sqrt();
+            if correlation > max_correlation.0 {
+                max_correlation = (correlation, far_length - near_length - start);
+            }
+        }
+        max_correlation
+    }
+
+    fn is_audible(&self, energy: f32, length: usize) -> bool {
+        levels::to_db((energy / length.max(1) as f32).sqrt()) >= self.config.min_level_db
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Noise;
+
+    const SAMPLE_RATE: usize = 44100;
+    const CHUNK: usize = 2048;
+
+    /// Low-passed noise with most of the energy below 1 kHz, like speech.
+    fn low_noise(seconds: f32, level: f32, seed: u32) -> Vec<f32> {
+        let mut noise = Noise::new(seed);
+        let mut value = 0.0;
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|_| {
+            value = value * 0.9 + noise.white() * 0.1;
+            value * level
+        }).collect()
+    }
+
+    /// Runs the detector over the mix sent to the participant and the participant's audio, returns
+    /// whether the echo was found.
+    fn detect(far: &[f32], near: &[f32]) -> bool {
+        let mut detector = EchoDetector::new(&EchoDetectionConfig::default(), SAMPLE_RATE);
+        let mut is_echo = false;
+        for (far_chunk, near_chunk) in far.chunks(CHUNK).zip(near.chunks(CHUNK)) {
+            if let Some(echo) = detector.process(near_chunk, near_chunk.len()) {
+                is_echo = echo;
+            }
+            detector.sent(far_chunk);
+        }
+        is_echo
+    }
+
+    #[test]
+    fn test_echo() {
+        let far = low_noise(4.0, 0.5, 1);
+        let delay = SAMPLE_RATE * 3 / 10;
+        let mut noise = Noise::new(2);
+        let near: Vec<f32> = (0..far.len())
+            .map(|i| if i >= delay { far[i - delay] * 0.3 } else { 0.0 } + noise.white() * 0.002)
+            .collect();
+        assert!(detect(&far, &near));
+    }
+
+    #[test]
+    fn test_no_echo() {
+        let far = low_noise(4.0, 0.5, 1);
+        let near = low_noise(4.0, 0.5, 3);
+        assert!(!detect(&far, &near));
+        let silence = vec![0.0; far.len()];
+        assert!(!detect(&far, &silence));
+        assert!(!detect(&silence, &near));
+    }
+
+    #[test]
+    fn test_delay() {
+        let config = EchoDetectionConfig::default();
+        let mut detector = EchoDetector::new(&config, SAMPLE_RATE);
+        let signal: Vec<f32> = low_noise(4.0, 0.5, 4).chunks(DECIMATION)
+            .map(|block| block.iter().sum::<f32>() / DECIMATION as f32)
+            .collect();
+        let far_length = detector.window_length + detector.max_lag;
+        for lag in [0, 100, 500, detector.max_lag] {
+            detector.far = signal[..far_length].iter().copied().collect();
+            let end = far_length - lag;
+            detector.near = signal[end - detector.window_length..end].iter().map(|v| v * 0.5).collect();
+            let (correlation, found_lag) = detector.max_correlation();
+            assert_eq!(found_lag, lag);
+            assert!(correlation > 0.99, "correlation {} at lag {}", correlation, lag);
+        }
+    }
+}
@@ -1,10 +1,25 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use async_trait::async_trait;
 use chrono::Utc;
 use log::{debug, error, info};
 use tokio::sync::mpsc::Sender;
+use tokio::task;
 
+use crate::ambience::DuckingConfig;
+use crate::bot::{RecorderBot, TestToneBot, VirtualParticipant};
+use crate::breakout;
+use crate::breakout::Breakouts;
 use crate::dto;
-use crate::easymund::{ChatMessage, Context, Participant};
+use crate::easymund::{is_virtual_client, ChatMessage, Context, Participant, Room, SAMPLE_RATE};
+use crate::interpretation;
+use crate::media;
+use crate::media::MediaPlayback;
+use crate::session;
+use crate::spatial::Position;
 use crate::wsserver::WSClientEvent;
 
 struct ClientEvent {
@@ -24,38 +39,93 @@ struct JoinHandler {}
 impl Handler for JoinHandler {
     async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
                     -> Vec<ClientEvent> {
+        let resumed = match &event.resume_token {
+            Some(resume_token) => context.resume_client(client_id, room_id, resume_token).await,
+            None => None,
+        };
         let mut first_in_room = false;
+        let mut is_stage = false;
         if let Some(room) = context.rooms.lock().await.get(room_id) {
-            first_in_room = !room.clients.iter().any(|&other_client_id| client_id != other_client_id);
+            first_in_room = !room.has_other_participants(client_id);
+            is_stage = room.stage.is_some();
         }
         if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
-            let participant = Participant {
-                name: event.participant.unwrap_or_default().name.unwrap_or(format!("{}", client_id)),
-                is_admin: first_in_room, is_muted: true, is_sharing: false,
-            };
-            info!("Client {}: {:?}", client_id, &participant);
-            client.participant = Some(participant);
+            if resumed.is_none() {
+                let participant = Participant {
+                    name: event.participant.unwrap_or_default().name.unwrap_or(format!("{}", client_id)),
+                    is_admin: first_in_room, is_muted: true, is_sharing: false, can_use_soundboard: false,
+                    is_bot: false, position: None, is_listener: is_stage && !first_in_room,
+                    interpreter_channel: None, listen_channel: None, is_reconnecting: false, is_deafened: false,
+                };
+                info!("Client {}: {:?}", client_id, &participant);
+                client.participant = Some(participant);
+                client.resume_token.get_or_insert_with(session::new_resume_token);
+            }
+            if event.audio_channels == Some(2) {
+                if let Err(e) = client.set_stereo_output() {
+                    error!("Failed to switch client {} to stereo: {:?}", client_id, e);
+                }
+            }
+        }
+        if resumed.is_none() {
+            JoinHandler::play_chime(room_id, context).await;
         }
         JoinHandler::join_events(client_id, room_id, context).await
     }
 }
 
 impl JoinHandler {
+    async fn play_chime(room_id: &str, context: &Context) {
+        if let Some(chime) = &context.soundboard_config.join_chime {
+            if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+                room.play_effect(chime);
+            }
+        }
+    }
+
     async fn join_events(new_client_id: u64, room_id: &str, context: &Context) -> Vec<ClientEvent> {
         let mut participants = Vec::new();
         let mut other_clients_ids = Vec::new();
         let mut chat = Vec::new();
         let mut ambience = None;
+        let mut scene = Vec::new();
+        let mut ducking = dto::Ducking::default();
+        let mut media_state = dto::Media::default();
+        let mut echo = None;
+        let mut is_spatial = false;
+        let mut is_stage = false;
+        let mut floor = None;
+        let mut hands = Vec::new();
+        let mut channels = Vec::new();
+        let mut breakout = None;
+        let mut media_files = media::list_files(&context.media_config.media_path);
         let mut room_name = None;
-        if let Some(room) = context.rooms.lock().await.get(room_id) {
-            ambience = Some(room.ambience_id.clone());
+        let mut resume_token = None;
+        let rooms = context.rooms.lock().await;
+        if let Some(room) = rooms.get(room_id) {
+            media_state = room.media_state();
+            echo = room.echo_state();
+            is_spatial = room.is_spatial;
+            is_stage = room.stage.is_some();
+            floor = room.floor.as_ref().map(|floor| floor.state());
+            hands = room.hands.clone();
+            channels = room.channels.clone();
+            breakout = rooms.get(room.parent.as_deref().unwrap_or(room_id))
+                .and_then(|parent| parent.breakouts.as_ref())
+                .map(|breakouts| breakouts.state());
+            media_files.extend(room.uploads.iter().map(|(file, _)| file.clone()));
+            ambience = room.active_layers().next().map(|layer| layer.playback.ambience_id.clone());
+            scene = scene_convert(room);
+            ducking = ducking_convert(&room.ducker.config);
             room_name = Some(room.name.clone());
             for client_id in &room.clients {
                 if let Some(client) = context.clients.lock().await.get(client_id) {
                     if let Some(participant) = &client.participant {
-                        participants.push(participant_convert(*client_id, participant));
+                        participants.push(room_participant_convert(*client_id, participant, room));
                         if *client_id != new_client_id {
                             other_clients_ids.push(*client_id);
+                        } else {
+                            resume_token = client.resume_token.clone();
                         }
                     }
                 }
@@ -64,16 +134,38 @@ impl JoinHandler {
                 chat.push(chat_msg_convert(message))
             }
         }
+        drop(rooms);
         let ambiences = context.ambiences.iter().map(|ambience| dto::Ambience {
             id: ambience.id.clone(),
             name: ambience.name.clone(),
+            names: ambience.names.clone(),
+            category: ambience.category.clone(),
+            params: ambience.generator.as_ref().map(|generator| generator.values()),
+        }).collect();
+        let sounds = context.effects.iter().map(|effect| dto::Sound {
+            id: effect.id.clone(),
+            name: effect.name.clone(),
+            names: effect.names.clone(),
         }).collect();
 
+        let mut room_event = dto::room(new_client_id, room_name.unwrap_or_default(), participants.clone(), ambiences, ambience, chat);
+        room_event.scene = Some(scene);
+        room_event.ducking = Some(ducking);
+        room_event.sounds = Some(sounds);
+        room_event.media = Some(media_state);
+        room_event.media_files = Some(media_files);
+        room_event.echo = echo;
+        room_event.is_spatial = Some(is_spatial);
+        room_event.is_stage = Some(is_stage);
+        room_event.floor = floor;
+        room_event.hands = Some(hands);
+        room_event.channels = Some(channels);
+        room_event.breakout = breakout;
+        room_event.room_id = Some(String::from(room_id));
+        room_event.resume_token = resume_token;
+
         let mut events = Vec::with_capacity(other_clients_ids.len() + 1);
-        events.push(ClientEvent {
-            client_id: new_client_id,
-            event: dto::room(new_client_id, room_name.unwrap_or_default(), participants.clone(), ambiences, ambience, chat),
-        });
+        events.push(ClientEvent {client_id: new_client_id, event: room_event});
         for client_id in other_clients_ids {
             events.push(ClientEvent {client_id, event: dto::participants(participants.clone())});
         }
@@ -85,9 +177,85 @@ struct LeaveHandler {}
 
 #[async_trait]
 impl Handler for LeaveHandler {
-    async fn handle(&self, _: u64, room_id: &str, _: dto::EasymundEvent, context: &Context)
+    async fn handle(&self, client_id: u64, room_id: &str, _: dto::EasymundEvent, context: &Context)
                     -> Vec<ClientEvent> {
-        EventHandler::update_room_participants(room_id, context, None).await
+        let has_left = context.leave_room(client_id).await.is_some();
+        if has_left {
+            LeaveHandler::play_chime(room_id, context).await;
+        }
+        let mut events = EventHandler::update_room_left(room_id, context).await;
+        if has_left {
+            events.push(ClientEvent {client_id, event: dto::leave()});
+        }
+        events
+    }
+}
+
+impl LeaveHandler {
+    async fn play_chime(room_id: &str, context: &Context) {
+        if let Some(chime) = &context.soundboard_config.leave_chime {
+            if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+                room.play_effect(chime);
+            }
+        }
+    }
+}
+
+/// Switches the connection to another room. The participant keeps its name and audio settings,
+/// the admin and stage roles are set by the new room.
+struct JoinRoomHandler {}
+
+#[async_trait]
+impl Handler for JoinRoomHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, mut event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let has_participant = context.clients.lock().await.get(&client_id).is_some_and(|client| client.participant.is_some());
+        if !has_participant {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Сначала войдите в конференцию"))}];
+        }
+        let new_room_id = match event.room_id.take().filter(|id| !id.is_empty()) {
+            Some(id) => id,
+            None => return vec![ClientEvent {client_id, event: dto::error_event(String::from("Не указана конференция"))}],
+        };
+        if new_room_id == room_id {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Вы уже в этой конференции"))}];
+        }
+        if let Err(error) = JoinRoomHandler::check_room(client_id, &new_room_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(error)}];
+        }
+
+        let mut events = Vec::new();
+        if let Some(old_room_id) = context.leave_room(client_id).await {
+            LeaveHandler::play_chime(&old_room_id, context).await;
+            events.extend(EventHandler::update_room_left(&old_room_id, context).await);
+        }
+        if !context.enter_room(client_id, &new_room_id).await {
+            events.push(ClientEvent {client_id, event: dto::error_event(format!("Конференция {} не существует", &new_room_id))});
+            return events;
+        }
+        JoinHandler::play_chime(&new_room_id, context).await;
+        events.extend(JoinHandler::join_events(client_id, &new_room_id, context).await);
+        events
+    }
+}
+
+impl JoinRoomHandler {
+    /// Sound check rooms are private, a breakout room is entered only by the participants assigned to it.
+    async fn check_room(client_id: u64, room_id: &str, context: &Context) -> Result<(), String> {
+        let rooms = context.rooms.lock().await;
+        let room = rooms.get(room_id).ok_or_else(|| format!("Конференция {} не существует", room_id))?;
+        if room.echo.is_some() {
+            return Err(String::from("Нельзя войти в комнату проверки звука"));
+        }
+        if let Some(parent_id) = &room.parent {
+            let is_assigned = rooms.get(parent_id)
+                .and_then(|parent| parent.breakouts.as_ref())
+                .is_some_and(|breakouts| breakouts.assignments.get(&client_id).is_some_and(|id| id == room_id));
+            if !is_assigned {
+                return Err(String::from("Вы не распределены в этот сессионный зал"));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -99,16 +267,92 @@ impl Handler for AmbienceHandler {
                     -> Vec<ClientEvent> {
         let ambience = event.ambience.unwrap_or_default();
         let mut clients_ids = Vec::new();
+        let mut scene = Vec::new();
         if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
-            room.ambience_id = ambience.clone();
-            room.ambience_position = 0;
+            room.set_scene(&[dto::SceneLayer {ambience: ambience.clone(), volume: 1.0, params: None}]);
+            scene = scene_convert(room);
             for client_id in &room.clients {
                 clients_ids.push(*client_id);
             }
         }
-        let mut events = Vec::with_capacity(clients_ids.len());
+        let mut events = Vec::with_capacity(clients_ids.len() * 2);
         for client_id in clients_ids {
             events.push(ClientEvent {client_id, event: dto::ambience(ambience.clone())});
+            events.push(ClientEvent {client_id, event: dto::scene(scene.clone())});
+        }
+        events
+    }
+}
+
+struct SceneHandler {}
+
+#[async_trait]
+impl Handler for SceneHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Менять фоновые звуки может только администратор"))}];
+        }
+        let mut layers = Vec::new();
+        for layer in event.scene.unwrap_or_default() {
+            if !context.ambiences.iter().any(|ambience| ambience.id == layer.ambience) {
+                error!("Unknown ambience {} in room {} scene", &layer.ambience, room_id);
+            } else if !layers.iter().any(|other: &dto::SceneLayer| other.ambience == layer.ambience) {
+                layers.push(dto::SceneLayer {volume: layer.volume.clamp(0.0, 1.0), ..layer});
+            }
+        }
+        let mut clients_ids = Vec::new();
+        let mut scene = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            room.set_scene(&layers);
+            scene = scene_convert(room);
+            for client_id in &room.clients {
+                clients_ids.push(*client_id);
+            }
+        }
+        let mut events = Vec::with_capacity(clients_ids.len());
+        for client_id in clients_ids {
+            events.push(ClientEvent {client_id, event: dto::scene(scene.clone())});
+        }
+        events
+    }
+}
+
+struct DuckingHandler {}
+
+#[async_trait]
+impl Handler for DuckingHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Настраивать приглушение фона может только администратор"))}];
+        }
+        let event_ducking = event.ducking.unwrap_or_default();
+        let mut clients_ids = Vec::new();
+        let mut ducking = dto::Ducking::default();
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            let config = &mut room.ducker.config;
+            if let Some(enabled) = event_ducking.enabled {
+                config.enabled = enabled;
+            }
+            if let Some(depth_db) = event_ducking.depth_db {
+                config.depth_db = depth_db.clamp(0.0, 60.0);
+            }
+            if let Some(attack_ms) = event_ducking.attack_ms {
+                config.attack_ms = attack_ms.min(10_000);
+            }
+            if let Some(release_ms) = event_ducking.release_ms {
+                config.release_ms = release_ms.min(10_000);
+            }
+            info!("Room {} ambience ducking: {:?}", room_id, config);
+            ducking = ducking_convert(config);
+            for client_id in &room.clients {
+                clients_ids.push(*client_id);
+            }
+        }
+        let mut events = Vec::with_capacity(clients_ids.len());
+        for client_id in clients_ids {
+            events.push(ClientEvent {client_id, event: dto::ducking(ducking.clone())});
         }
         events
     }
@@ -122,7 +366,12 @@ impl Handler for ParticipantHandler {
                     -> Vec<ClientEvent> {
         let event_participant = event.participant.unwrap_or_default();
         let id = event_participant.id.unwrap_or(client_id);
+        let is_requester_admin = EventHandler::is_admin(client_id, context).await;
         let mut change_admin = false;
+        let mut is_promoted = false;
+        let (room_channels, is_stage) = context.rooms.lock().await.get(room_id)
+            .map(|room| (room.channels.clone(), room.stage.is_some()))
+            .unwrap_or_default();
         debug!("Target client id {}", id);
         if let Some(client) = context.clients.lock().await.get_mut(&id) {
             if let Some(participant) = &mut client.participant {
@@ -142,12 +391,54 @@ impl Handler for ParticipantHandler {
                         info!("Participant {} is sharing screen: {}", &participant.name, participant.is_sharing);
                     }
                 }
+                if let Some(can_use_soundboard) = event_participant.can_use_soundboard {
+                    if !is_requester_admin {
+                        error!("Client {} is not admin to change soundboard permission", client_id);
+                    } else if participant.can_use_soundboard != can_use_soundboard {
+                        participant.can_use_soundboard = can_use_soundboard;
+                        info!("Participant {} can use soundboard: {}", &participant.name, participant.can_use_soundboard);
+                    }
+                }
+                if let Some(is_deafened) = event_participant.is_deafened {
+                    if id != client_id {
+                        error!("Client {} can't change the sound of participant {}", client_id, id);
+                    } else if participant.is_deafened != is_deafened {
+                        participant.is_deafened = is_deafened;
+                        info!("Participant {} is deafened: {}", &participant.name, participant.is_deafened);
+                    }
+                }
+                if let Some(is_listener) = event_participant.is_listener {
+                    if !is_requester_admin {
+                        error!("Client {} is not admin to move participants to or from the stage", client_id);
+                    } else if !is_stage {
+                        error!("Room {} has no stage to move participant {} to or from", room_id, id);
+                    } else if participant.is_listener != is_listener {
+                        participant.is_listener = is_listener;
+                        is_promoted = !is_listener;
+                        info!("Participant {} is listener: {}", &participant.name, participant.is_listener);
+                    }
+                }
+                if let Some(channel) = &event_participant.interpreter_channel {
+                    if !is_requester_admin {
+                        error!("Client {} is not admin to assign interpreters", client_id);
+                    } else if !channel.is_empty() && !room_channels.contains(channel) {
+                        error!("Unknown interpretation channel {} in room {}", channel, room_id);
+                    } else {
+                        participant.interpreter_channel = Some(channel.clone()).filter(|channel| !channel.is_empty());
+                        info!("Participant {} interprets to {:?}", &participant.name, &participant.interpreter_channel);
+                    }
+                }
             }
         }
         if change_admin {
             ParticipantHandler::change_room_admin(id, room_id, context).await;
         }
-        EventHandler::update_room_participants(room_id, context, None).await
+        let mut events = Vec::new();
+        if is_promoted && HandHandler::lower_hand(id, room_id, context).await {
+            events.extend(EventHandler::update_room_hands(room_id, context).await);
+        }
+        events.extend(EventHandler::update_room_participants(room_id, context, None).await);
+        events
     }
 }
 
@@ -172,6 +463,623 @@ impl ParticipantHandler {
     }
 }
 
+struct SoundHandler {}
+
+#[async_trait]
+impl Handler for SoundHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let sound = event.sound.unwrap_or_default();
+        if !context.effects.iter().any(|effect| effect.id == sound) {
+            return vec![ClientEvent {client_id, event: dto::error_event(format!("Звук {} не существует", &sound))}];
+        }
+        let min_interval = Duration::from_millis(context.soundboard_config.min_interval_ms);
+        let mut sound_participant = None;
+        let mut error = None;
+        if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
+            if let Some(participant) = &client.participant {
+                if !participant.is_admin && !participant.can_use_soundboard {
+                    error = Some(String::from("Нет разрешения на проигрывание звуков"));
+                } else if client.last_sound_time.is_some_and(|time| time.elapsed() < min_interval) {
+                    error = Some(String::from("Слишком частое проигрывание звуков"));
+                } else {
+                    sound_participant = Some(participant_convert(client_id, participant));
+                    client.last_sound_time = Some(Instant::now());
+                }
+            }
+        }
+        if let Some(error) = error {
+            return vec![ClientEvent {client_id, event: dto::error_event(error)}];
+        }
+
+        let mut events = Vec::new();
+        if let Some(participant) = sound_participant {
+            if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+                info!("Participant {:?} plays sound {} in room {}", &participant.name, &sound, room_id);
+                room.play_effect(&sound);
+                for client_id in &room.clients {
+                    events.push(ClientEvent {client_id: *client_id, event: dto::sound(sound.clone(), participant.clone())});
+                }
+            }
+        }
+        events
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum MediaAction {
+    Play, Pause, Seek, Stop, Upload,
+}
+
+struct MediaHandler {
+    action: MediaAction,
+}
+
+#[async_trait]
+impl Handler for MediaHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Управлять воспроизведением может только администратор"))}];
+        }
+        if let MediaAction::Upload = self.action {
+            let token = context.rooms.lock().await.get_mut(room_id).map(|room| room.issue_upload_token());
+            let media = dto::Media {upload_token: token, ..Default::default()};
+            return vec![ClientEvent {client_id, event: dto::media(media)}];
+        }
+        let event_media = event.media.unwrap_or_default();
+        let mut loaded = None;
+        if let (MediaAction::Play, Some(file)) = (self.action, &event_media.file) {
+            let upload = context.rooms.lock().await.get(room_id).and_then(|room| room.upload(file));
+            let data = match upload {
+                Some(data) => Ok(data),
+                None => MediaHandler::read_media_file(file, context).await,
+            };
+            match data {
+                Ok(data) => loaded = Some(MediaPlayback::new(file, data)),
+                Err(e) => {
+                    error!("Failed to read media {}: {:?}", file, e);
+                    return vec![ClientEvent {client_id, event: dto::error_event(format!("Не удалось открыть файл {}", file))}];
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            if loaded.is_some() {
+                room.media = loaded;
+            }
+            let position = event_media.position.map(|position| (position.max(0.0) * SAMPLE_RATE as f32) as usize);
+            match (self.action, &mut room.media) {
+                (MediaAction::Play, Some(media)) => {
+                    if let Some(position) = position {
+                        media.seek(position);
+                    } else if media.is_finished() {
+                        media.seek(0);
+                    }
+                    media.is_playing = true;
+                }
+                (MediaAction::Pause, Some(media)) => media.is_playing = false,
+                (MediaAction::Seek, Some(media)) => media.seek(position.unwrap_or_default()),
+                (MediaAction::Stop, _) => room.media = None,
+                _ => {}
+            }
+            info!("Room {} media {:?}: {:?}", room_id, self.action, room.media_state());
+            let media_state = room.media_state();
+            for client_id in &room.clients {
+                events.push(ClientEvent {client_id: *client_id, event: dto::media(media_state.clone())});
+            }
+        }
+        events
+    }
+}
+
+impl MediaHandler {
+    async fn read_media_file(file: &str, context: &Context) -> Result<Arc<Vec<f32>>, Box<dyn Error>> {
+        let media_path = context.media_config.media_path.clone();
+        let file = String::from(file);
+        let data = task::spawn_blocking(move || media::read_file(&media_path, &file, SAMPLE_RATE)
+            .map_err(|e| e.to_string())).await??;
+        Ok(Arc::new(data))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum HandAction {
+    Raise, Lower, Next, Clear,
+}
+
+struct HandHandler {
+    action: HandAction,
+}
+
+#[async_trait]
+impl Handler for HandHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let target_id = event.participant.and_then(|participant| participant.id).unwrap_or(client_id);
+        let is_moderated = match self.action {
+            HandAction::Raise => false,
+            HandAction::Lower => target_id != client_id,
+            HandAction::Next | HandAction::Clear => true,
+        };
+        if is_moderated && !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Управлять очередью может только модератор"))}];
+        }
+
+        let mut is_changed = false;
+        let mut called = None;
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            match self.action {
+                HandAction::Raise => is_changed = room.raise_hand(client_id),
+                HandAction::Lower => is_changed = room.lower_hand(target_id),
+                HandAction::Next => {
+                    called = room.hands.first().copied();
+                    is_changed = called.is_some_and(|id| room.lower_hand(id));
+                }
+                HandAction::Clear => {
+                    is_changed = !room.hands.is_empty();
+                    room.hands.clear();
+                }
+            }
+            if is_changed {
+                info!("Room {} hands {:?}: {:?}", room_id, self.action, &room.hands);
+            }
+        }
+        if let Some(called) = called {
+            if let Some(participant) = context.clients.lock().await.get_mut(&called).and_then(|client| client.participant.as_mut()) {
+                participant.is_muted = false;
+                participant.is_listener = false;
+                info!("Participant {} is called to speak", &participant.name);
+            }
+        }
+        if !is_changed {
+            return Vec::new();
+        }
+        let mut events = EventHandler::update_room_hands(room_id, context).await;
+        events.extend(EventHandler::update_room_participants(room_id, context, None).await);
+        events
+    }
+}
+
+impl HandHandler {
+    async fn lower_hand(client_id: u64, room_id: &str, context: &Context) -> bool {
+        context.rooms.lock().await.get_mut(room_id).is_some_and(|room| room.lower_hand(client_id))
+    }
+}
+
+struct WhisperHandler {
+    is_start: bool,
+}
+
+#[async_trait]
+impl Handler for WhisperHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let event_whisper = event.whisper.unwrap_or_default();
+        let speaker = event_whisper.from.unwrap_or(client_id);
+        if speaker != client_id && !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Направлять звук других участников может только модератор"))}];
+        }
+        let mut notified = Vec::new();
+        let mut targets = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            if !room.clients.contains(&speaker) {
+                return vec![ClientEvent {client_id, event: dto::error_event(String::from("Участник не найден"))}];
+            }
+            let previous = if self.is_start {
+                let whisper_targets: HashSet<u64> = event_whisper.targets.iter().copied()
+                    .filter(|id| *id != speaker && room.clients.contains(id))
+                    .collect();
+                if whisper_targets.is_empty() {
+                    return vec![ClientEvent {client_id, event: dto::error_event(String::from("Не выбраны участники для разговора"))}];
+                }
+                room.routing.whisper(speaker, whisper_targets)
+            } else {
+                room.routing.stop_whisper(speaker)
+            };
+            targets = room.routing.targets(speaker);
+            info!("Room {} participant {} whispers to {:?}", room_id, speaker, &targets);
+            notified.push(speaker);
+            if client_id != speaker {
+                notified.push(client_id);
+            }
+            for id in previous.into_iter().chain(targets.iter().copied()) {
+                if !notified.contains(&id) {
+                    notified.push(id);
+                }
+            }
+        }
+        notified.into_iter().map(|id| ClientEvent {
+            client_id: id,
+            event: dto::whisper(dto::Whisper {from: Some(speaker), targets: targets.clone()}),
+        }).collect()
+    }
+}
+
+struct ChannelsHandler {}
+
+#[async_trait]
+impl Handler for ChannelsHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Менять каналы перевода может только администратор"))}];
+        }
+        let mut channels: Vec<String> = Vec::new();
+        for channel in event.channels.unwrap_or_default() {
+            if !interpretation::is_valid_channel(&channel) {
+                return vec![ClientEvent {client_id, event: dto::error_event(format!("Неверное имя канала {}", &channel))}];
+            }
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+        if channels.len() > context.interpretation_config.max_channels {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Слишком много каналов перевода"))}];
+        }
+
+        let mut clients_ids = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            room.channels = channels.clone();
+            clients_ids.extend(room.clients.iter().copied());
+            info!("Room {} interpretation channels: {:?}", room_id, &room.channels);
+        }
+        let mut events = Vec::new();
+        for client_id in clients_ids {
+            events.push(ClientEvent {client_id, event: dto::channels(channels.clone())});
+            if let Some(participant) = context.clients.lock().await.get_mut(&client_id).and_then(|client| client.participant.as_mut()) {
+                participant.interpreter_channel = participant.interpreter_channel.take().filter(|channel| channels.contains(channel));
+                if participant.listen_channel.as_ref().is_some_and(|channel| !channels.contains(channel)) {
+                    participant.listen_channel = None;
+                    events.push(ClientEvent {client_id, event: dto::channel(None)});
+                }
+            }
+        }
+        events.extend(EventHandler::update_room_participants(room_id, context, None).await);
+        events
+    }
+}
+
+struct ChannelHandler {}
+
+#[async_trait]
+impl Handler for ChannelHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let channel = event.channel.filter(|channel| !channel.is_empty());
+        let room_channels = context.rooms.lock().await.get(room_id).map(|room| room.channels.clone()).unwrap_or_default();
+        let is_known = channel.as_ref().is_none_or(|channel| room_channels.contains(channel));
+        if !is_known {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Канал перевода не существует"))}];
+        }
+        if let Some(participant) = context.clients.lock().await.get_mut(&client_id).and_then(|client| client.participant.as_mut()) {
+            info!("Participant {} listens to channel {:?}", &participant.name, &channel);
+            participant.listen_channel = channel.clone();
+        }
+        vec![ClientEvent {client_id, event: dto::channel(channel)}]
+    }
+}
+
+struct FloorHandler {
+    is_request: bool,
+}
+
+#[async_trait]
+impl Handler for FloorHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let mut target_id = client_id;
+        if let Some(id) = event.participant.and_then(|participant| participant.id).filter(|_| !self.is_request) {
+            if id != client_id && !EventHandler::is_admin(client_id, context).await {
+                return vec![ClientEvent {client_id, event: dto::error_event(String::from("Забрать слово у участника может только администратор"))}];
+            }
+            target_id = id;
+        }
+        let mut is_changed = false;
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            match &mut room.floor {
+                Some(floor) => {
+                    is_changed = if self.is_request { floor.request(target_id) } else { floor.release(target_id) };
+                    if is_changed {
+                        info!("Room {} floor holder {:?}, queue {:?}", room_id, floor.holder, floor.queue);
+                    }
+                }
+                None => return vec![ClientEvent {client_id, event: dto::error_event(String::from("В конференции нет очереди на слово"))}],
+            }
+        }
+        if is_changed {
+            EventHandler::update_room_floor(room_id, context).await
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BreakoutAction {
+    Create, Assign, Start, Close,
+}
+
+struct BreakoutHandler {
+    action: BreakoutAction,
+}
+
+#[async_trait]
+impl Handler for BreakoutHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        // The admin of a sub-room is just the first one who entered it, only the admin of the meeting moderates
+        let is_parent = context.rooms.lock().await.get(room_id).is_some_and(|room| room.parent.is_none());
+        if !is_parent || !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Управлять сессионными залами может только модератор"))}];
+        }
+        let event_breakout = event.breakout.unwrap_or_default();
+        let parent_id = room_id;
+        let result = match self.action {
+            BreakoutAction::Create => BreakoutHandler::create(parent_id, event_breakout, context).await,
+            BreakoutAction::Assign => BreakoutHandler::assign(parent_id, event_breakout, context).await,
+            BreakoutAction::Start => BreakoutHandler::start(parent_id, event_breakout, context).await,
+            BreakoutAction::Close => BreakoutHandler::close(parent_id, context).await,
+        };
+        match result {
+            Ok(events) => events,
+            Err(error) => vec![ClientEvent {client_id, event: dto::error_event(error)}],
+        }
+    }
+}
+
+impl BreakoutHandler {
+    async fn create(parent_id: &str, event_breakout: dto::Breakout, context: &Context)
+                    -> Result<Vec<ClientEvent>, String> {
+        let names: Vec<String> = match event_breakout.rooms {
+            Some(rooms) => rooms.into_iter().map(|room| room.name).collect(),
+            None => (1..=event_breakout.count.unwrap_or_default()).map(|i| format!("Зал {}", i)).collect(),
+        };
+        if names.is_empty() || names.len() > context.breakout_config.max_rooms {
+            return Err(String::from("Неверное количество сессионных залов"));
+        }
+        {
+            let mut rooms = context.rooms.lock().await;
+            let parent = rooms.get(parent_id).ok_or_else(|| String::from("Конференция не найдена"))?;
+            if parent.breakouts.is_some() {
+                return Err(String::from("Сессионные залы уже созданы"));
+            }
+            let mut breakout_rooms = Vec::with_capacity(names.len());
+            let mut new_rooms = Vec::with_capacity(names.len());
+            for (i, name) in names.into_iter().enumerate() {
+                let id = format!("{}-{}", parent_id, i + 1);
+                new_rooms.push(Room::new_breakout(id.clone(), name.clone(), parent));
+                breakout_rooms.push((id, name));
+            }
+            info!("Room {} breakout rooms: {:?}", parent_id, &breakout_rooms);
+            for room in new_rooms {
+                rooms.insert(room.id.clone(), room);
+            }
+            if let Some(parent) = rooms.get_mut(parent_id) {
+                parent.breakouts = Some(Breakouts::new(breakout_rooms, &context.breakout_config));
+            }
+        }
+        Ok(EventHandler::update_room_breakout(parent_id, context).await)
+    }
+
+    /// Assigns the participants of the meeting to the sub-rooms, once the session is started they are moved at once.
+    async fn assign(parent_id: &str, event_breakout: dto::Breakout, context: &Context) -> Result<Vec<ClientEvent>, String> {
+        let mut parent_clients = Vec::new();
+        let mut meeting_clients = HashSet::new();
+        let mut rooms_ids = Vec::new();
+        {
+            let rooms = context.rooms.lock().await;
+            if let Some(breakouts) = rooms.get(parent_id).and_then(|room| room.breakouts.as_ref()) {
+                rooms_ids.extend(breakouts.rooms.iter().map(|(id, _)| id.clone()));
+            }
+            if let Some(room) = rooms.get(parent_id) {
+                parent_clients.extend(room.clients.iter().copied());
+            }
+            // Participants already moved to the sub-rooms can be reassigned while the session is open
+            for room_id in rooms_ids.iter().map(String::as_str).chain([parent_id]) {
+                if let Some(room) = rooms.get(room_id) {
+                    meeting_clients.extend(room.clients.iter().copied().filter(|id| !is_virtual_client(*id)));
+                }
+            }
+        }
+        if rooms_ids.is_empty() {
+            return Err(String::from("Сессионные залы не созданы"));
+        }
+
+        let assignments = if event_breakout.is_random == Some(true) {
+            let clients = context.clients.lock().await;
+            let mut participants: Vec<u64> = parent_clients.into_iter()
+                .filter(|id| !is_virtual_client(*id))
+                .filter(|id| clients.get(id).and_then(|client| client.participant.as_ref()).is_some_and(|participant| !participant.is_admin))
+                .collect();
+            participants.sort();
+            breakout::assign_randomly(&participants, &rooms_ids)
+        } else {
+            let mut assignments = event_breakout.assignments.unwrap_or_default();
+            if let Some(room_id) = assignments.values().find(|room_id| !rooms_ids.contains(room_id)) {
+                return Err(format!("Сессионный зал {} не существует", room_id));
+            }
+            assignments.retain(|client_id, _| {
+                let is_participant = meeting_clients.contains(client_id);
+                if !is_participant {
+                    error!("Client {} is not a participant of room {} to assign to a breakout room", client_id, parent_id);
+                }
+                is_participant
+            });
+            assignments
+        };
+
+        let mut is_started = false;
+        if let Some(breakouts) = context.rooms.lock().await.get_mut(parent_id).and_then(|room| room.breakouts.as_mut()) {
+            if event_breakout.is_random == Some(true) {
+                breakouts.assignments.clear();
+            }
+            breakouts.assignments.extend(assignments.clone());
+            is_started = breakouts.is_started();
+            info!("Room {} breakout assignments: {:?}", parent_id, &breakouts.assignments);
+        }
+        let mut events = Vec::new();
+        if is_started {
+            for (client_id, room_id) in assignments {
+                events.extend(EventHandler::move_client(client_id, &room_id, context).await);
+            }
+        }
+        events.extend(EventHandler::update_room_breakout(parent_id, context).await);
+        Ok(events)
+    }
+
+    async fn start(parent_id: &str, event_breakout: dto::Breakout, context: &Context) -> Result<Vec<ClientEvent>, String> {
+        let mut assignments = Vec::new();
+        match context.rooms.lock().await.get_mut(parent_id).and_then(|room| room.breakouts.as_mut()) {
+            Some(breakouts) if breakouts.is_started() => return Err(String::from("Сессионные залы уже открыты")),
+            Some(breakouts) => {
+                breakouts.start(event_breakout.duration_s.map(Duration::from_secs));
+                assignments.extend(breakouts.assignments.iter().map(|(client_id, room_id)| (*client_id, room_id.clone())));
+                info!("Room {} breakout rooms opened for {:?} s", parent_id, event_breakout.duration_s);
+            }
+            None => return Err(String::from("Сессионные залы не созданы")),
+        }
+        let mut events = Vec::new();
+        for (client_id, room_id) in assignments {
+            events.extend(EventHandler::move_client(client_id, &room_id, context).await);
+        }
+        events.extend(EventHandler::update_room_breakout(parent_id, context).await);
+        Ok(events)
+    }
+
+    /// Starts the countdown before the return to the meeting, the sub-rooms which were not opened are removed at once.
+    async fn close(parent_id: &str, context: &Context) -> Result<Vec<ClientEvent>, String> {
+        let is_started = match context.rooms.lock().await.get_mut(parent_id).and_then(|room| room.breakouts.as_mut()) {
+            Some(breakouts) => {
+                if breakouts.is_started() {
+                    breakouts.close();
+                }
+                breakouts.is_started()
+            }
+            None => return Err(String::from("Сессионные залы не созданы")),
+        };
+        if is_started {
+            Ok(EventHandler::update_room_breakout(parent_id, context).await)
+        } else {
+            Ok(EventHandler::end_breakouts(parent_id, context).await)
+        }
+    }
+}
+
+struct PositionHandler {}
+
+#[async_trait]
+impl Handler for PositionHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let room_clients = match context.rooms.lock().await.get(room_id) {
+            Some(room) if room.is_spatial => room.clients.clone(),
+            _ => {
+                let error = String::from("В этой конференции нет пространственного звука");
+                return vec![ClientEvent {client_id, event: dto::error_event(error)}];
+            }
+        };
+        let position = event.position.map(|position| Position {x: position.x, y: position.y})
+            .filter(|position| position.is_valid());
+        if position.is_none() {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Неверная позиция"))}];
+        }
+        let mut events = Vec::new();
+        let mut clients = context.clients.lock().await;
+        let Some(participant) = clients.get_mut(&client_id).and_then(|client| client.participant.as_mut()) else {
+            return events;
+        };
+        participant.position = position;
+        let position_participant = dto::Participant {id: Some(client_id), position: event.position, ..Default::default()};
+        let recipients = room_clients.into_iter()
+            .filter(|id| !is_virtual_client(*id) && clients.get(id).is_some_and(|client| client.is_online()));
+        for recipient_id in recipients {
+            events.push(ClientEvent {client_id: recipient_id, event: dto::position(position_participant.clone())});
+        }
+        events
+    }
+}
+
+struct EchoHandler {}
+
+#[async_trait]
+impl Handler for EchoHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        let event_echo = event.echo.unwrap_or_default();
+        let mut echo_state = None;
+        if let Some(room) = context.rooms.lock().await.get_mut(room_id) {
+            if let Some(echo) = &mut room.echo {
+                if let Some(delay_ms) = event_echo.delay_ms {
+                    echo.set_delay(delay_ms.min(context.echo_config.max_delay_ms));
+                }
+                if let Some(test_tone) = event_echo.test_tone {
+                    echo.set_tone(&context.echo_config, test_tone);
+                }
+                echo_state = room.echo_state();
+                info!("Room {} echo: {:?}", room_id, &echo_state);
+            }
+        }
+        match echo_state {
+            Some(echo) => vec![ClientEvent {client_id, event: dto::echo(echo)}],
+            None => vec![ClientEvent {client_id, event: dto::error_event(String::from("Конференция не является проверкой звука"))}],
+        }
+    }
+}
+
+struct BotHandler {
+    is_add: bool,
+}
+
+#[async_trait]
+impl Handler for BotHandler {
+    async fn handle(&self, client_id: u64, room_id: &str, event: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if !EventHandler::is_admin(client_id, context).await {
+            return vec![ClientEvent {client_id, event: dto::error_event(String::from("Управлять ботами может только администратор"))}];
+        }
+        let event_bot = event.bot.unwrap_or_default();
+        if self.is_add {
+            let bot: Box<dyn VirtualParticipant> = match event_bot.kind.as_deref() {
+                Some("tone") => Box::new(TestToneBot::new(event_bot.frequency.unwrap_or(440.0).clamp(20.0, 20_000.0), 0.1, SAMPLE_RATE)),
+                Some("recorder") => match RecorderBot::new(&context.media_config.recordings_path, room_id, SAMPLE_RATE) {
+                    Ok(bot) => Box::new(bot),
+                    Err(e) => {
+                        error!("Failed to start recording room {}: {:?}", room_id, e);
+                        return vec![ClientEvent {client_id, event: dto::error_event(String::from("Не удалось начать запись"))}];
+                    }
+                },
+                kind => return vec![ClientEvent {client_id, event: dto::error_event(format!("Неизвестный бот {:?}", kind))}],
+            };
+            context.attach_virtual_participant(room_id, bot).await;
+        } else if let Some(id) = event_bot.id {
+            let bot_room_id = context.clients.lock().await.get(&id).and_then(|client| client.room.clone());
+            if bot_room_id.as_deref() != Some(room_id) {
+                return vec![ClientEvent {client_id, event: dto::error_event(String::from("Бот не найден в конференции"))}];
+            }
+            context.detach_virtual_participant(id).await;
+        }
+        EventHandler::update_room_participants(room_id, context, None).await
+    }
+}
+
+struct LevelsHandler {
+    is_subscribe: bool,
+}
+
+#[async_trait]
+impl Handler for LevelsHandler {
+    async fn handle(&self, client_id: u64, _: &str, _: dto::EasymundEvent, context: &Context)
+                    -> Vec<ClientEvent> {
+        if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
+            client.is_levels_subscribed = self.is_subscribe;
+            info!("Client {} is subscribed to levels: {}", client_id, self.is_subscribe);
+        }
+        Vec::new()
+    }
+}
+
 struct ChatHandler {}
 
 #[async_trait]
@@ -214,6 +1122,41 @@ fn participant_convert(client_id: u64, participant: &Participant) -> dto::Partic
         is_admin: Some(participant.is_admin),
         is_muted: Some(participant.is_muted),
         is_sharing: Some(participant.is_sharing),
+        can_use_soundboard: Some(participant.can_use_soundboard),
+        is_bot: Some(participant.is_bot),
+        is_listener: Some(participant.is_listener),
+        is_reconnecting: Some(participant.is_reconnecting),
+        is_deafened: Some(participant.is_deafened),
+        interpreter_channel: participant.interpreter_channel.clone(),
+        position: participant.position.map(|position| dto::Position {x: position.x, y: position.y}),
+        ..Default::default()
+    }
+}
+
+/// Participant with the state kept by the room.
+fn room_participant_convert(client_id: u64, participant: &Participant, room: &Room) -> dto::Participant {
+    let hand_position = room.hand_position(client_id);
+    dto::Participant {
+        is_hand_raised: Some(hand_position.is_some()),
+        hand_position,
+        ..participant_convert(client_id, participant)
+    }
+}
+
+fn scene_convert(room: &Room) -> Vec<dto::SceneLayer> {
+    room.active_layers().map(|layer| dto::SceneLayer {
+        ambience: layer.playback.ambience_id.clone(),
+        volume: layer.volume,
+        params: if layer.params.is_empty() { None } else { Some(layer.params.clone()) },
+    }).collect()
+}
+
+fn ducking_convert(config: &DuckingConfig) -> dto::Ducking {
+    dto::Ducking {
+        enabled: Some(config.enabled),
+        depth_db: Some(config.depth_db),
+        attack_ms: Some(config.attack_ms),
+        release_ms: Some(config.release_ms),
     }
 }
 
@@ -236,12 +1179,12 @@ impl EventHandler {
         let mut clients_ids = Vec::new();
         if let Some(room) = context.rooms.lock().await.get(room_id) {
             for client_id in &room.clients {
-                if except_client.map_or(false, |id| *client_id == id) {
+                if except_client == Some(*client_id) {
                     continue;
                 }
                 if let Some(client) = context.clients.lock().await.get(client_id) {
                     if let Some(participant) = &client.participant {
-                        participants.push(participant_convert(*client_id, participant));
+                        participants.push(room_participant_convert(*client_id, participant, room));
                         clients_ids.push(*client_id);
                     }
                 }
@@ -255,13 +1198,158 @@ impl EventHandler {
         events
     }
 
+    async fn update_room_hands(room_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get(room_id) {
+            for client_id in &room.clients {
+                events.push(ClientEvent {client_id: *client_id, event: dto::hands(room.hands.clone())});
+            }
+        }
+        events
+    }
+
+    async fn update_room_floor(room_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+        if let Some(room) = context.rooms.lock().await.get(room_id) {
+            if let Some(floor) = &room.floor {
+                let floor = floor.state();
+                for client_id in &room.clients {
+                    events.push(ClientEvent {client_id: *client_id, event: dto::floor(floor.clone())});
+                }
+            }
+        }
+        events
+    }
+
+    /// State of the breakout session for the meeting and all its sub-rooms.
+    async fn update_room_breakout(parent_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+        let rooms = context.rooms.lock().await;
+        if let Some(breakouts) = rooms.get(parent_id).and_then(|room| room.breakouts.as_ref()) {
+            let state = breakouts.state();
+            let rooms_ids = std::iter::once(parent_id).chain(breakouts.rooms.iter().map(|(id, _)| id.as_str()));
+            for room in rooms_ids.filter_map(|room_id| rooms.get(room_id)) {
+                for client_id in &room.clients {
+                    events.push(ClientEvent {client_id: *client_id, event: dto::breakout(state.clone())});
+                }
+            }
+        }
+        events
+    }
+
+    /// Pulls everybody back to the meeting and removes the sub-rooms.
+    async fn end_breakouts(parent_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let breakouts = context.rooms.lock().await.get_mut(parent_id).and_then(|room| room.breakouts.take());
+        let mut events = Vec::new();
+        let Some(breakouts) = breakouts else {
+            return events;
+        };
+        for (room_id, _) in &breakouts.rooms {
+            let clients_ids: Vec<u64> = context.rooms.lock().await.get(room_id)
+                .map(|room| room.clients.iter().copied().collect())
+                .unwrap_or_default();
+            for client_id in clients_ids {
+                if is_virtual_client(client_id) {
+                    context.detach_virtual_participant(client_id).await;
+                } else {
+                    events.extend(EventHandler::move_client(client_id, parent_id, context).await);
+                }
+            }
+            context.rooms.lock().await.remove(room_id);
+        }
+        info!("Room {} breakout rooms closed", parent_id);
+        if let Some(room) = context.rooms.lock().await.get(parent_id) {
+            let state = dto::Breakout {rooms: Some(Vec::new()), is_started: Some(false), ..Default::default()};
+            for client_id in &room.clients {
+                events.push(ClientEvent {client_id: *client_id, event: dto::breakout(state.clone())});
+            }
+        }
+        events
+    }
+
+    /// Moves the client to the room and sends the room state to it, the rooms on both sides get the new participants.
+    async fn move_client(client_id: u64, room_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let Some(old_room_id) = context.move_client(client_id, room_id).await else {
+            return Vec::new();
+        };
+        let mut events = JoinHandler::join_events(client_id, room_id, context).await;
+        events.extend(EventHandler::update_room_left(&old_room_id, context).await);
+        events
+    }
+
+    /// Participants, raised hands and floor of the room after somebody has left it.
+    async fn update_room_left(room_id: &str, context: &Context) -> Vec<ClientEvent> {
+        let mut events = EventHandler::update_room_participants(room_id, context, None).await;
+        events.extend(EventHandler::update_room_hands(room_id, context).await);
+        events.extend(EventHandler::update_room_floor(room_id, context).await);
+        events
+    }
+
+    pub async fn handle_breakouts(context: &Context, sender: &Sender<WSClientEvent>) {
+        let mut countdowns = Vec::new();
+        let mut finished = Vec::new();
+        for room in context.rooms.lock().await.values_mut() {
+            if let Some(breakouts) = &mut room.breakouts {
+                if breakouts.is_over() {
+                    finished.push(room.id.clone());
+                } else if let Some(remaining_s) = breakouts.countdown_tick() {
+                    debug!("Room {} breakout rooms close in {} s", &room.id, remaining_s);
+                    countdowns.push(room.id.clone());
+                }
+            }
+        }
+        let mut events = Vec::new();
+        for parent_id in countdowns {
+            events.extend(EventHandler::update_room_breakout(&parent_id, context).await);
+        }
+        for parent_id in finished {
+            events.extend(EventHandler::end_breakouts(&parent_id, context).await);
+        }
+        EventHandler::send(&events, context, sender).await;
+    }
+
+    async fn is_admin(client_id: u64, context: &Context) -> bool {
+        context.clients.lock().await.get(&client_id)
+            .and_then(|client| client.participant.as_ref())
+            .is_some_and(|participant| participant.is_admin)
+    }
 
     fn get_handler(event: &str) -> Option<&dyn Handler> {
         match event {
             "join" => Some(&JoinHandler{}),
+            "join_room" => Some(&JoinRoomHandler{}),
+            "leave" => Some(&LeaveHandler{}),
             "ambience" => Some(&AmbienceHandler{}),
+            "scene" => Some(&SceneHandler{}),
+            "ducking" => Some(&DuckingHandler{}),
             "participant" => Some(&ParticipantHandler{}),
             "chat" => Some(&ChatHandler{}),
+            "sound" => Some(&SoundHandler{}),
+            "media_play" => Some(&MediaHandler{action: MediaAction::Play}),
+            "media_pause" => Some(&MediaHandler{action: MediaAction::Pause}),
+            "media_seek" => Some(&MediaHandler{action: MediaAction::Seek}),
+            "media_stop" => Some(&MediaHandler{action: MediaAction::Stop}),
+            "media_upload" => Some(&MediaHandler{action: MediaAction::Upload}),
+            "echo" => Some(&EchoHandler{}),
+            "position" => Some(&PositionHandler{}),
+            "floor_request" => Some(&FloorHandler{is_request: true}),
+            "floor_release" => Some(&FloorHandler{is_request: false}),
+            "raise_hand" => Some(&HandHandler{action: HandAction::Raise}),
+            "lower_hand" => Some(&HandHandler{action: HandAction::Lower}),
+            "hand_next" => Some(&HandHandler{action: HandAction::Next}),
+            "hand_clear" => Some(&HandHandler{action: HandAction::Clear}),
+            "channels" => Some(&ChannelsHandler{}),
+            "channel" => Some(&ChannelHandler{}),
+            "whisper" => Some(&WhisperHandler{is_start: true}),
+            "whisper_stop" => Some(&WhisperHandler{is_start: false}),
+            "breakout_create" => Some(&BreakoutHandler{action: BreakoutAction::Create}),
+            "breakout_assign" => Some(&BreakoutHandler{action: BreakoutAction::Assign}),
+            "breakout_start" => Some(&BreakoutHandler{action: BreakoutAction::Start}),
+            "breakout_close" => Some(&BreakoutHandler{action: BreakoutAction::Close}),
+            "levels_subscribe" => Some(&LevelsHandler{is_subscribe: true}),
+            "levels_unsubscribe" => Some(&LevelsHandler{is_subscribe: false}),
+            "bot_add" => Some(&BotHandler{is_add: true}),
+            "bot_remove" => Some(&BotHandler{is_add: false}),
             _ => None
         }
     }
@@ -272,13 +1360,17 @@ impl EventHandler {
                 debug!("Client {}: {:?}", client_id, &event);
                 if let Some(handler) = EventHandler::get_handler(event.event.clone().as_str()) {
                     let mut room_id = None;
-                    if let Some(client) = context.clients.lock().await.get(&client_id) {
+                    if let Some(client) = context.clients.lock().await.get_mut(&client_id) {
+                        client.activity.touch();
                         room_id = Some(client.room.clone());
                     }
-                    if let Some(room_id) = room_id {
-                        EventHandler::handle_and_send(client_id, &room_id, event, handler, context, sender).await;
-                    } else {
-                        error!("Unknown client {}", client_id);
+                    match room_id {
+                        Some(Some(room_id)) => EventHandler::handle_and_send(client_id, &room_id, event, handler, context, sender).await,
+                        Some(None) if event.event == "join_room" => {
+                            EventHandler::handle_and_send(client_id, "", event, handler, context, sender).await;
+                        }
+                        Some(None) => error!("Client {} is not in a room, event '{}' is ignored", client_id, &event.event),
+                        None => error!("Unknown client {}", client_id),
                     }
                 } else {
                     error!("Unknown event '{}'", &event.event);
@@ -293,7 +1385,17 @@ impl EventHandler {
     async fn handle_and_send(client_id: u64, room_id: &str, event: dto::EasymundEvent, handler: &dyn Handler,
                              context: &Context, sender: &Sender<WSClientEvent>) {
         let events = handler.handle(client_id, room_id, event, context).await;
-        for event in &events {
+        EventHandler::send(&events, context, sender).await;
+    }
+
+    /// Sends the events to the connected clients, bots and clients waiting for reconnect are skipped.
+    async fn send(events: &[ClientEvent], context: &Context, sender: &Sender<WSClientEvent>) {
+        let offline_clients: HashSet<u64> = context.clients.lock().await.iter()
+            .filter(|(_, client)| !client.is_online())
+            .map(|(client_id, _)| *client_id)
+            .collect();
+        let events = events.iter().filter(|event| !is_virtual_client(event.client_id) && !offline_clients.contains(&event.client_id));
+        for event in events {
             let json = serde_json::to_string(&event.event).unwrap();
             if let Err(e) = sender.send(WSClientEvent {
                 client_id: event.client_id,
@@ -306,7 +1408,13 @@ impl EventHandler {
         }
     }
 
+    pub async fn handle_participants_update(room_id: &str, context: &Context, sender: &Sender<WSClientEvent>) {
+        let events = EventHandler::update_room_participants(room_id, context, None).await;
+        EventHandler::send(&events, context, sender).await;
+    }
+
     pub async fn handle_room_update(client_id: u64, room_id: &str, context: &Context, sender: &Sender<WSClientEvent>) {
-        EventHandler::handle_and_send(client_id, room_id, dto::leave(), &LeaveHandler{}, context, sender).await;
+        let events = LeaveHandler{}.handle(client_id, room_id, dto::leave(), context).await;
+        EventHandler::send(&events, context, sender).await;
     }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::dto;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FloorConfig {
+    pub max_floor_ms: u64,
+}
+
+impl Default for FloorConfig {
+    fn default() -> Self {
+        Self {max_floor_ms: 30_000}
+    }
+}
+
+/// Half-duplex room mode: only the floor holder is heard, other participants wait in the queue.
+pub struct FloorControl {
+    pub holder: Option<u64>,
+    pub queue: VecDeque<u64>,
+    granted_at: Instant,
+    max_floor_time: Duration,
+}
+
+impl FloorControl {
+    pub fn new(config: &FloorConfig) -> Self {
+        Self {
+            holder: None,
+            queue: VecDeque::new(),
+            granted_at: Instant::now(),
+            max_floor_time: Duration::from_millis(config.max_floor_ms),
+        }
+    }
+
+    pub fn is_audible(&self, client_id: u64) -> bool {
+        self.holder == Some(client_id)
+    }
+
+    /// Gives the floor to the participant when it is free, otherwise puts the participant in the queue.
+    /// Returns `true` when the state was changed.
+    pub fn request(&mut self, client_id: u64) -> bool {
+        if self.holder.is_none() {
+            self.grant(Some(client_id));
+            true
+        } else if self.holder != Some(client_id) && !self.queue.contains(&client_id) {
+            self.queue.push_back(client_id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes the floor from the participant or removes the participant from the queue.
+    /// Returns `true` when the state was changed.
+    pub fn release(&mut self, client_id: u64) -> bool {
+        if self.holder == Some(client_id) {
+            let next = self.queue.pop_front();
+            self.grant(next);
+            true
+        } else if let Some(index) = self.queue.iter().position(|&id| id == client_id) {
+            self.queue.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gives the floor or the place in the queue to the participant's new connection.
+    pub fn replace(&mut self, old_id: u64, new_id: u64) {
+        if self.holder == Some(old_id) {
+            self.holder = Some(new_id);
+        }
+        for id in self.queue.iter_mut().filter(|id| **id == old_id) {
+            *id = new_id;
+        }
+    }
+
+    /// Passes the floor to the next in the queue when the holder has talked for too long.
+    /// Returns `true` when the floor was taken.
+    pub fn check_timeout(&mut self) -> bool {
+        match self.holder {
+            Some(holder) if self.granted_at.elapsed() >= self.max_floor_time => self.release(holder),
+            _ => false,
+        }
+    }
+
+    pub fn state(&self) -> dto::Floor {
+        dto::Floor {
+            holder: self.holder,
+            queue: self.queue.iter().copied().collect(),
+            max_floor_ms: self.max_floor_time.as_millis() as u64,
+            remaining_ms: self.holder.map(|_| self.max_floor_time.saturating_sub(self.granted_at.elapsed()).as_millis() as u64),
+        }
+    }
+
+    fn grant(&mut self, client_id: Option<u64>) {
+        self.holder = client_id;
+        self.granted_at = Instant::now();
+    }
+}
@@ -15,7 +15,7 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio_rustls::TlsAcceptor;
 
 enum HTTPStatus {
-    Ok = 200, NotFound = 404, MethodNotAllowed = 405,
+    Ok = 200, BadRequest = 400, NotFound = 404, MethodNotAllowed = 405, PayloadTooLarge = 413,
 }
 
 #[derive(Debug)]
@@ -27,8 +27,10 @@ impl HTTPStatus {
     fn get_message(&self) -> String {
         match self {
             HTTPStatus::Ok => {String::from("OK")}
+            HTTPStatus::BadRequest => {String::from("Bad Request")}
             HTTPStatus::NotFound => {String::from("Not Found")}
             HTTPStatus::MethodNotAllowed => {String::from("Method Not Allowed")}
+            HTTPStatus::PayloadTooLarge => {String::from("Payload Too Large")}
         }
     }
 }
@@ -45,6 +47,9 @@ pub struct HTTPReq {
 #[async_trait]
 pub trait PostHandler: Send + Sync {
     async fn handle(&self, path: &str, req_body: &[u8]) -> Option<Vec<u8>>;
+
+    /// Largest request body accepted for the path, checked against `Content-Length` before the body is read.
+    fn max_body_size(&self, path: &str) -> usize;
 }
 
 impl HTTPServer {
@@ -91,7 +96,7 @@ impl HTTPServer {
         for entry in fs::read_dir(content_path)?.flatten() {
             match HTTPServer::read_dir_entry(prefix, &entry) {
                 Ok(res) => {
-                    result.extend(res.into_iter());
+                    result.extend(res);
                 }
                 Err(e) => {
                     error!("Failed to read entry {:?}: {:?}", &entry, e);
@@ -153,13 +158,26 @@ impl HTTPServer {
                         }
                     }
                     HTTPMethod::Post => {
-                        let l = req.headers.get("Content-Length").unwrap();
-                        let content_length = l.parse::<usize>().unwrap();
-                        let req_body = HTTPServer::read_req_body(content_length, reader).await?;
-                        if let Some(resp_body) = handler.handle(&path, &req_body).await {
-                            HTTPServer::generate_response(HTTPStatus::Ok, "resp.json", &resp_body, keep_alive)
-                        } else {
-                            HTTPServer::generate_response(HTTPStatus::NotFound, "", &[0], keep_alive)
+                        let content_length = req.headers.get("Content-Length").and_then(|l| l.parse::<usize>().ok());
+                        match content_length {
+                            Some(content_length) if content_length <= handler.max_body_size(&path) => {
+                                let req_body = HTTPServer::read_req_body(content_length, reader).await?;
+                                if let Some(resp_body) = handler.handle(&path, &req_body).await {
+                                    HTTPServer::generate_response(HTTPStatus::Ok, "resp.json", &resp_body, keep_alive)
+                                } else {
+                                    HTTPServer::generate_response(HTTPStatus::NotFound, "", &[0], keep_alive)
+                                }
+                            }
+                            // The body is left unread, so the connection can't be reused
+                            Some(content_length) => {
+                                info!("{:?}: POST '{}' body of {} bytes is too large", addr.ip(), &path, content_length);
+                                keep_alive = false;
+                                HTTPServer::generate_response(HTTPStatus::PayloadTooLarge, "", &[0], keep_alive)
+                            }
+                            None => {
+                                keep_alive = false;
+                                HTTPServer::generate_response(HTTPStatus::BadRequest, "", &[0], keep_alive)
+                            }
                         }
                     }
                     HTTPMethod::Unknown => {
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct InterpretationConfig {
+    pub floor_duck_db: f32,
+    pub max_channels: usize,
+}
+
+impl Default for InterpretationConfig {
+    fn default() -> Self {
+        Self {floor_duck_db: 15.0, max_channels: 8}
+    }
+}
+
+impl InterpretationConfig {
+    /// Gain of the floor audio for listeners of an interpretation channel.
+    pub fn floor_gain(&self) -> f32 {
+        10_f32.powf(-self.floor_duck_db.max(0.0) / 20.0)
+    }
+}
+
+pub fn is_valid_channel(channel: &str) -> bool {
+    !channel.is_empty() && channel.len() <= 32 && channel.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
@@ -0,0 +1,61 @@
+use serde::Deserialize;
+
+use crate::dto;
+
+pub const CLIPPING_LEVEL: f32 = 0.99;
+const SILENCE_DB: f32 = -100.0;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LevelsConfig {
+    pub interval_ms: u64,
+}
+
+impl Default for LevelsConfig {
+    fn default() -> Self {
+        Self {interval_ms: 100}
+    }
+}
+
+/// Accumulates the level of the audio between two measurements.
+#[derive(Default)]
+pub struct LevelMeter {
+    peak: f32,
+    square_sum: f32,
+    measured: usize,
+    clipped: usize,
+}
+
+impl LevelMeter {
+    pub fn measure(&mut self, chunk: &[f32]) {
+        for v in chunk {
+            self.peak = self.peak.max(v.abs());
+            self.square_sum += v * v;
+            if v.abs() >= CLIPPING_LEVEL {
+                self.clipped += 1;
+            }
+        }
+        self.measured += chunk.len();
+    }
+
+    /// Returns the level of the audio measured so far and starts a new measurement.
+    pub fn take(&mut self) -> dto::Level {
+        let rms = (self.square_sum / self.measured.max(1) as f32).sqrt();
+        let level = dto::Level {
+            rms_db: to_db(rms),
+            peak_db: to_db(self.peak),
+            is_clipping: self.clipped > 0,
+        };
+        *self = LevelMeter::default();
+        level
+    }
+}
+
+pub fn to_db(value: f32) -> f32 {
+    if value > 0.0 { (20.0 * value.log10()).max(SILENCE_DB) } else { SILENCE_DB }
+}
+
+/// Compact form of the participant level for the `levels` event: id, RMS and peak in whole dBFS.
+pub fn compact(client_id: u64, level: &dto::Level) -> (u64, i8, i8) {
+    (client_id, level.rms_db.round() as i8, level.peak_db.round() as i8)
+}
@@ -5,7 +5,7 @@ use log::{error, info};
 use serde::Deserialize;
 use tokio::sync::mpsc;
 
-use crate::easymund::Easymund;
+use crate::easymund::{Easymund, EasymundConfig};
 use crate::httpserver::HTTPServer;
 use crate::wsserver::WSServer;
 
@@ -15,10 +15,31 @@ mod httpserver;
 mod dto;
 mod event_handler;
 mod ambience;
+mod procedural;
+mod soundboard;
+mod media;
+mod bot;
+mod echo;
+mod spatial;
+mod floor;
+mod stage;
+mod interpretation;
+mod routing;
+mod breakout;
+mod session;
+mod activity;
+mod levels;
+mod vad;
+mod quality;
+mod echo_detection;
+#[cfg(test)]
+mod test_util;
 
 #[derive(Deserialize, Debug)]
 struct Config {
     http: HttpConfig,
+    #[serde(flatten)]
+    easymund: EasymundConfig,
 }
 
 #[derive(Deserialize, Debug)]
@@ -42,7 +63,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
             error!("Failed to start WebSocket server: {:?}", e);
         }
     });
-    let easymund = Easymund::create();
+    let easymund = Easymund::create(config.easymund);
     let post_handler = easymund.get_post_handler();
     if config.http.is_secure {
         tokio::spawn(async {
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use easymund_audio_codec::media::MediaDecoder;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MediaConfig {
+    pub media_path: String,
+    pub max_upload_mb: usize,
+    /// Uploaded files kept per room, the oldest one is dropped for a new upload.
+    pub max_uploads: usize,
+    pub recordings_path: String,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {media_path: String::from("media"), max_upload_mb: 50, max_uploads: 5, recordings_path: String::from("recordings")}
+    }
+}
+
+/// Audio file played by the server into a room as if it were a participant.
+pub struct MediaPlayback {
+    pub file: String,
+    pub data: Arc<Vec<f32>>,
+    pub position: usize,
+    pub is_playing: bool,
+}
+
+impl MediaPlayback {
+    pub fn new(file: &str, data: Arc<Vec<f32>>) -> Self {
+        Self {file: String::from(file), data, position: 0, is_playing: true}
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        self.position = position.min(self.data.len());
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    pub fn next_chunk(&mut self, samples_count: usize) -> Vec<f32> {
+        if !self.is_playing || self.is_finished() {
+            return Vec::new();
+        }
+        let end = (self.position + samples_count).min(self.data.len());
+        let mut chunk = Vec::with_capacity(samples_count);
+        chunk.extend_from_slice(&self.data[self.position..end]);
+        chunk.resize(samples_count, 0.0);
+        self.position = end;
+        if self.is_finished() {
+            self.is_playing = false;
+        }
+        chunk
+    }
+}
+
+pub fn is_valid_file_name(file: &str) -> bool {
+    !file.is_empty() && !file.starts_with('.') && !file.contains(['/', '\\'])
+}
+
+pub fn list_files(media_path: &str) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(media_path).map(|entries| entries.flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|file| is_valid_file_name(file))
+        .collect()
+    ).unwrap_or_default();
+    files.sort();
+    files
+}
+
+pub fn read_file(media_path: &str, file: &str, sample_rate: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+    if !is_valid_file_name(file) {
+        Err(format!("Invalid media file name {}", file))?;
+    }
+    Ok(MediaDecoder::new(sample_rate).decode_file(&Path::new(media_path).join(file))?.to_mono())
+}
+
+pub fn decode_upload(file: &str, data: Vec<u8>, sample_rate: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+    let extension = Path::new(file).extension().and_then(|e| e.to_str());
+    Ok(MediaDecoder::new(sample_rate).decode_bytes(data, extension)?.to_mono())
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorKind {
+    Pink,
+    Brown,
+    Rain,
+    Wind,
+    RoomTone,
+    Murmur,
+}
+
+/// Parameters of a procedural ambience, every value is in range 0..1.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GeneratorParams {
+    pub kind: GeneratorKind,
+    #[serde(default = "GeneratorParams::default_value")]
+    pub intensity: f32,
+    #[serde(default = "GeneratorParams::default_value")]
+    pub brightness: f32,
+    #[serde(default = "GeneratorParams::default_value")]
+    pub variation: f32,
+}
+
+impl GeneratorParams {
+    pub const NAMES: [&'static str; 3] = ["intensity", "brightness", "variation"];
+
+    fn default_value() -> f32 {
+        0.5
+    }
+
+    /// Sets one of `NAMES`, the caller checks the name against the list.
+    pub fn set(&mut self, name: &str, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        match name {
+            "intensity" => self.intensity = value,
+            "brightness" => self.brightness = value,
+            "variation" => self.variation = value,
+            _ => {}
+        }
+    }
+
+    pub fn values(&self) -> HashMap<String, f32> {
+        HashMap::from([
+            (String::from("intensity"), self.intensity),
+            (String::from("brightness"), self.brightness),
+            (String::from("variation"), self.variation),
+        ])
+    }
+}
+
+/// Synthesises noise textures in real time for the room mixer.
+pub struct ProceduralGenerator {
+    pub params: GeneratorParams,
+    sample_rate: f32,
+    noise: Noise,
+    tone_filter: OnePole,
+    bright_filter: OnePole,
+    drop_filter: OnePole,
+    drop_envelope: f32,
+    modulation: SlowNoise,
+    voices: Vec<Voice>,
+}
+
+impl ProceduralGenerator {
+    pub fn new(params: &GeneratorParams, sample_rate: usize, seed: u32) -> Self {
+        let mut noise = Noise::new(seed);
+        let sample_rate = sample_rate as f32;
+        let voices = (0..12).map(|_| Voice::new(&mut noise, sample_rate)).collect();
+        Self {
+            params: params.clone(),
+            sample_rate,
+            noise,
+            tone_filter: OnePole::default(),
+            bright_filter: OnePole::default(),
+            drop_filter: OnePole::default(),
+            drop_envelope: 0.0,
+            modulation: SlowNoise::new(sample_rate, 0.3),
+            voices,
+        }
+    }
+
+    pub fn next_chunk(&mut self, samples_count: usize) -> Vec<f32> {
+        let mut chunk = Vec::with_capacity(samples_count);
+        for _ in 0..samples_count {
+            let v = match self.params.kind {
+                GeneratorKind::Pink => self.pink(),
+                GeneratorKind::Brown => self.brown(),
+                GeneratorKind::Rain => self.rain(),
+                GeneratorKind::Wind => self.wind(),
+                GeneratorKind::RoomTone => self.room_tone(),
+                GeneratorKind::Murmur => self.murmur(),
+            };
+            chunk.push(v.clamp(-1.0, 1.0));
+        }
+        chunk
+    }
+
+    /// Cutoff frequency in range 200 Hz .. 12.8 kHz controlled by the brightness.
+    fn bright_cutoff(&self) -> f32 {
+        200.0 * 2_f32.powf(self.params.brightness * 6.0)
+    }
+
+    fn pink(&mut self) -> f32 {
+        let cutoff = self.bright_cutoff();
+        let pink = self.noise.pink();
+        self.bright_filter.lowpass(pink, cutoff, self.sample_rate) * (0.2 + 0.8 * self.params.intensity)
+    }
+
+    fn brown(&mut self) -> f32 {
+        let cutoff = self.bright_cutoff();
+        let brown = self.noise.brown();
+        self.bright_filter.lowpass(brown, cutoff, self.sample_rate) * (0.2 + 0.8 * self.params.intensity)
+    }
+
+    fn rain(&mut self) -> f32 {
+        let modulation = 1.0 + self.params.variation * self.modulation.next(&mut self.noise);
+        let cutoff = self.bright_cutoff();
+        let pink = self.noise.pink();
+        let bed = self.bright_filter.lowpass(pink, cutoff, self.sample_rate) * (0.15 + 0.35 * self.params.intensity);
+
+        let drops_rate = (20.0 + 400.0 * self.params.intensity) * modulation;
+        if self.noise.uniform() < drops_rate / self.sample_rate {
+            self.drop_envelope = self.drop_envelope.max(0.2 + 0.8 * self.noise.uniform());
+        }
+        self.drop_envelope *= (-1.0 / (0.004 * self.sample_rate)).exp();
+        let white = self.noise.white();
+        let drop = white - self.drop_filter.lowpass(white, 1500.0, self.sample_rate);
+        bed + drop * self.drop_envelope * 0.5
+    }
+
+    fn wind(&mut self) -> f32 {
+        let modulation = self.params.variation * self.modulation.next(&mut self.noise);
+        let cutoff = self.bright_cutoff() * 0.5 * (1.0 + 0.8 * modulation);
+        let brown = self.noise.brown();
+        let filtered = self.tone_filter.lowpass(brown, cutoff, self.sample_rate);
+        let filtered = self.bright_filter.lowpass(filtered, cutoff, self.sample_rate);
+        filtered * 0.8 * (0.3 + 0.7 * self.params.intensity) * (1.0 + 0.6 * modulation)
+    }
+
+    fn room_tone(&mut self) -> f32 {
+        let modulation = 1.0 + 0.3 * self.params.variation * self.modulation.next(&mut self.noise);
+        let cutoff = (150.0 + 1000.0 * self.params.brightness) * modulation;
+        let source = 0.7 * self.noise.brown() + 0.3 * self.noise.pink();
+        self.bright_filter.lowpass(source, cutoff, self.sample_rate) * (0.2 + 0.8 * self.params.intensity)
+    }
+
+    fn murmur(&mut self) -> f32 {
+        let active_voices = 2 + (self.params.intensity * (self.voices.len() - 2) as f32) as usize;
+        let brightness = self.params.brightness;
+        let variation = self.params.variation;
+        let mut sum = 0.0;
+        for voice in self.voices.iter_mut().take(active_voices) {
+            sum += voice.next(&mut self.noise, brightness, variation, self.sample_rate);
+        }
+        sum / (active_voices as f32).sqrt()
+    }
+}
+
+/// Random sources: white noise, pink noise (Paul Kellet filter) and brown noise (leaky integrator).
+pub struct Noise {
+    state: u32,
+    pink: [f32; 3],
+    brown: f32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self {state: seed.max(1), pink: [0.0; 3], brown: 0.0}
+    }
+
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32
+    }
+
+    pub fn white(&mut self) -> f32 {
+        self.uniform() * 2.0 - 1.0
+    }
+
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        self.pink[0] = 0.99765 * self.pink[0] + white * 0.0990460;
+        self.pink[1] = 0.96300 * self.pink[1] + white * 0.2965164;
+        self.pink[2] = 0.57000 * self.pink[2] + white * 1.0526913;
+        (self.pink[0] + self.pink[1] + self.pink[2] + white * 0.1848) * 0.15
+    }
+
+    fn brown(&mut self) -> f32 {
+        let white = self.white();
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+        self.brown * 3.5
+    }
+}
+
+#[derive(Default)]
+struct OnePole {
+    value: f32,
+}
+
+impl OnePole {
+    fn lowpass(&mut self, input: f32, cutoff: f32, sample_rate: f32) -> f32 {
+        let coefficient = 1.0 - (-2.0 * PI * cutoff / sample_rate).exp();
+        self.value += coefficient * (input - self.value);
+        self.value
+    }
+}
+
+/// Smooth random modulation in range -1..1 changing a few times per second.
+struct SlowNoise {
+    value: f32,
+    target: f32,
+    counter: usize,
+    period: usize,
+}
+
+impl SlowNoise {
+    fn new(sample_rate: f32, rate: f32) -> Self {
+        Self {value: 0.0, target: 0.0, counter: 0, period: (sample_rate / rate) as usize}
+    }
+
+    fn next(&mut self, noise: &mut Noise) -> f32 {
+        if self.counter == 0 {
+            self.target = noise.white();
+            self.counter = self.period;
+        }
+        self.counter -= 1;
+        self.value += (self.target - self.value) * 4.0 / self.period as f32;
+        self.value
+    }
+}
+
+/// A single talker of the murmur: band limited noise shaped by syllable rate envelope with pauses between phrases.
+struct Voice {
+    low_filter: OnePole,
+    high_filter: OnePole,
+    formant: f32,
+    phase: f32,
+    syllable_rate: f32,
+    activity: SlowNoise,
+    level: f32,
+}
+
+impl Voice {
+    fn new(noise: &mut Noise, sample_rate: f32) -> Self {
+        Self {
+            low_filter: OnePole::default(),
+            high_filter: OnePole::default(),
+            formant: 500.0 + 1500.0 * noise.uniform(),
+            phase: noise.uniform(),
+            syllable_rate: 3.0 + 3.0 * noise.uniform(),
+            activity: SlowNoise::new(sample_rate, 0.2 + 0.3 * noise.uniform()),
+            level: 0.0,
+        }
+    }
+
+    fn next(&mut self, noise: &mut Noise, brightness: f32, variation: f32, sample_rate: f32) -> f32 {
+        self.phase = (self.phase + self.syllable_rate / sample_rate).fract();
+        let syllable = (self.phase * PI).sin().powi(2);
+        let activity = self.activity.next(noise);
+        let activity = if activity > 0.3 * variation - 0.2 { 1.0 } else { 0.0 };
+        self.level += (activity - self.level) * 20.0 / sample_rate;
+        let white = noise.white();
+        let high = self.formant * (0.5 + brightness);
+        let band = self.low_filter.lowpass(white, high, sample_rate) - self.high_filter.lowpass(white, 250.0, sample_rate);
+        band * syllable * self.level * 1.6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::rms;
+
+    const SAMPLE_RATE: usize = 44100;
+    const KINDS: [GeneratorKind; 6] = [GeneratorKind::Pink, GeneratorKind::Brown, GeneratorKind::Rain,
+        GeneratorKind::Wind, GeneratorKind::RoomTone, GeneratorKind::Murmur];
+
+    fn params(kind: GeneratorKind, value: f32) -> GeneratorParams {
+        GeneratorParams {kind, intensity: value, brightness: value, variation: value}
+    }
+
+    #[test]
+    fn test_output_range() {
+        for kind in KINDS {
+            for value in [0.0, 0.5, 1.0] {
+                let mut generator = ProceduralGenerator::new(&params(kind, value), SAMPLE_RATE, 7);
+                let chunk = generator.next_chunk(SAMPLE_RATE * 5);
+                assert!(chunk.iter().all(|v| v.is_finite() && (-1.0..=1.0).contains(v)), "{:?} at {}", kind, value);
+                let level = rms(&chunk[SAMPLE_RATE..]);
+                assert!(level > 1e-4 && level < 0.5, "{:?} at {} has RMS {}", kind, value, level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_determinism() {
+        for kind in KINDS {
+            let mut first = ProceduralGenerator::new(&params(kind, 0.5), SAMPLE_RATE, 42);
+            let mut second = ProceduralGenerator::new(&params(kind, 0.5), SAMPLE_RATE, 42);
+            let mut other = ProceduralGenerator::new(&params(kind, 0.5), SAMPLE_RATE, 43);
+            let chunk = first.next_chunk(SAMPLE_RATE);
+            assert_eq!(chunk, second.next_chunk(SAMPLE_RATE), "{:?}", kind);
+            assert_ne!(chunk, other.next_chunk(SAMPLE_RATE), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_intensity_raises_level() {
+        for kind in KINDS {
+            let mut quiet = ProceduralGenerator::new(&params(kind, 0.5), SAMPLE_RATE, 3);
+            let mut loud = ProceduralGenerator::new(&params(kind, 0.5), SAMPLE_RATE, 3);
+            quiet.params.set("intensity", 0.0);
+            loud.params.set("intensity", 2.0);
+            assert_eq!(loud.params.intensity, 1.0);
+            let quiet_level = rms(&quiet.next_chunk(SAMPLE_RATE * 3));
+            let loud_level = rms(&loud.next_chunk(SAMPLE_RATE * 3));
+            assert!(loud_level > quiet_level, "{:?}: {} <= {}", kind, loud_level, quiet_level);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::levels;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Length of the analysis window, 0 disables the detection.
+    pub window_ms: u64,
+    /// Noise floor of a silent participant above which the background noise is reported.
+    pub noise_level_db: f32,
+    /// Part of the window the participant has to be silent over the noise.
+    pub noise_ratio: f32,
+    /// Part of the window with clipped audio.
+    pub clipping_ratio: f32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 10000,
+            noise_level_db: -50.0,
+            noise_ratio: 0.6,
+            clipping_ratio: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Issue {
+    Noise,
+    Echo,
+    Clipping,
+}
+
+impl Issue {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::Noise => "noise",
+            Issue::Echo => "echo",
+            Issue::Clipping => "clipping",
+        }
+    }
+}
+
+/// Finds problems with the participant's audio over windows of `window_ms`: steady background noise while the
+/// participant is silent and clipping. Echo is found by the echo detector, the issue is reported the same way.
+pub struct QualityMonitor {
+    window_samples: usize,
+    samples: usize,
+    ticks: usize,
+    noisy_ticks: usize,
+    clipped_ticks: usize,
+    issues: HashSet<Issue>,
+}
+
+impl QualityMonitor {
+    pub fn new(config: &QualityConfig, sample_rate: usize) -> Self {
+        Self {
+            window_samples: config.window_ms as usize * sample_rate / 1000,
+            samples: 0,
+            ticks: 0,
+            noisy_ticks: 0,
+            clipped_ticks: 0,
+            issues: HashSet::new(),
+        }
+    }
+
+    /// Analyses the participant's chunk of the tick. At the end of the window returns the issues which
+    /// were not found in the previous one.
+    pub fn analyze(&mut self, chunk: &[f32], samples_count: usize, is_talking: bool, noise_floor_db: f32,
+                   config: &QualityConfig) -> Vec<Issue> {
+        if self.window_samples == 0 {
+            return Vec::new();
+        }
+        self.samples += samples_count;
+        self.ticks += 1;
+        if !is_talking && noise_floor_db >= config.noise_level_db {
+            self.noisy_ticks += 1;
+        }
+        if chunk.iter().any(|v| v.abs() >= levels::CLIPPING_LEVEL) {
+            self.clipped_ticks += 1;
+        }
+        if self.samples < self.window_samples {
+            return Vec::new();
+        }
+
+        let mut issues = HashSet::new();
+        if self.noisy_ticks as f32 >= self.ticks as f32 * config.noise_ratio {
+            issues.insert(Issue::Noise);
+        }
+        if self.clipped_ticks as f32 >= self.ticks as f32 * config.clipping_ratio {
+            issues.insert(Issue::Clipping);
+        }
+        let mut new_issues: Vec<Issue> = issues.difference(&self.issues).copied().collect();
+        new_issues.sort_by_key(|issue| issue.kind());
+        self.issues = issues;
+        self.start_window();
+        new_issues
+    }
+
+    fn start_window(&mut self) {
+        self.samples = 0;
+        self.ticks = 0;
+        self.noisy_ticks = 0;
+        self.clipped_ticks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 44100;
+    const CHUNK: usize = 2048;
+
+    fn config() -> QualityConfig {
+        QualityConfig {window_ms: 1000, ..Default::default()}
+    }
+
+    fn chunk(level: f32) -> Vec<f32> {
+        (0..CHUNK).map(|i| (i as f32 * 0.05).sin() * level).collect()
+    }
+
+    /// Feeds one window of ticks, `tick` gives the chunk, talking state and noise floor of the tick by its number.
+    fn run_window(monitor: &mut QualityMonitor, config: &QualityConfig, tick: impl Fn(usize) -> (Vec<f32>, bool, f32))
+                  -> Vec<Issue> {
+        let ticks = (config.window_ms as usize * SAMPLE_RATE / 1000).div_ceil(CHUNK);
+        let mut issues = Vec::new();
+        for i in 0..ticks {
+            let (chunk, is_talking, noise_floor_db) = tick(i);
+            issues.extend(monitor.analyze(&chunk, CHUNK, is_talking, noise_floor_db, config));
+        }
+        issues
+    }
+
+    #[test]
+    fn test_noise_while_silent() {
+        let config = config();
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        let issues = run_window(&mut monitor, &config, |_| (chunk(0.01), false, -40.0));
+        assert_eq!(issues, vec![Issue::Noise]);
+    }
+
+    #[test]
+    fn test_no_noise_while_talking_or_quiet() {
+        let config = config();
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        assert!(run_window(&mut monitor, &config, |_| (chunk(0.3), true, -40.0)).is_empty());
+        assert!(run_window(&mut monitor, &config, |_| (chunk(0.001), false, -65.0)).is_empty());
+    }
+
+    #[test]
+    fn test_noise_ratio() {
+        let config = config();
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        // Silent over the noise in every other tick, below the ratio of 0.6
+        let issues = run_window(&mut monitor, &config, |i| (chunk(0.3), i.is_multiple_of(2), -40.0));
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_clipping() {
+        let config = config();
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        let clipped = |i: usize| {
+            let mut chunk = chunk(0.3);
+            if i.is_multiple_of(5) {
+                chunk[100] = 1.0;
+            }
+            (chunk, true, -70.0)
+        };
+        assert_eq!(run_window(&mut monitor, &config, clipped), vec![Issue::Clipping]);
+
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        let peak = |_| (chunk(0.95), true, -70.0);
+        assert!(run_window(&mut monitor, &config, peak).is_empty());
+    }
+
+    #[test]
+    fn test_only_new_issues_reported() {
+        let config = config();
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        let noisy = |_| (chunk(0.01), false, -40.0);
+        assert_eq!(run_window(&mut monitor, &config, noisy), vec![Issue::Noise]);
+        assert!(run_window(&mut monitor, &config, noisy).is_empty());
+        assert!(run_window(&mut monitor, &config, |_| (chunk(0.3), true, -40.0)).is_empty());
+        assert_eq!(run_window(&mut monitor, &config, noisy), vec![Issue::Noise]);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let config = QualityConfig {window_ms: 0, ..Default::default()};
+        let mut monitor = QualityMonitor::new(&config, SAMPLE_RATE);
+        for _ in 0..100 {
+            let mut chunk = chunk(0.01);
+            chunk[0] = 1.0;
+            assert!(monitor.analyze(&chunk, CHUNK, false, -20.0, &config).is_empty());
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::{HashMap, HashSet};
+
+/// Who hears whom in the room. By default everybody hears everybody, a participant whispering to a group
+/// is heard only by the participants of the group.
+#[derive(Default, Clone)]
+pub struct RoutingMatrix {
+    whispers: HashMap<u64, HashSet<u64>>,
+}
+
+impl RoutingMatrix {
+    pub fn can_hear(&self, listener: u64, speaker: u64) -> bool {
+        self.whispers.get(&speaker).is_none_or(|targets| targets.contains(&listener))
+    }
+
+    pub fn is_whispering(&self, speaker: u64) -> bool {
+        self.whispers.contains_key(&speaker)
+    }
+
+    pub fn targets(&self, speaker: u64) -> Vec<u64> {
+        let mut targets: Vec<u64> = self.whispers.get(&speaker).map(|targets| targets.iter().copied().collect()).unwrap_or_default();
+        targets.sort();
+        targets
+    }
+
+    /// Limits the speaker's audience to the targets, returns the previous targets.
+    pub fn whisper(&mut self, speaker: u64, targets: HashSet<u64>) -> Vec<u64> {
+        let previous = self.targets(speaker);
+        self.whispers.insert(speaker, targets);
+        previous
+    }
+
+    /// Makes the speaker heard by everybody again, returns the previous targets.
+    pub fn stop_whisper(&mut self, speaker: u64) -> Vec<u64> {
+        let previous = self.targets(speaker);
+        self.whispers.remove(&speaker);
+        previous
+    }
+
+    /// Moves the whispers of the participant to its new connection.
+    pub fn replace(&mut self, old_id: u64, new_id: u64) {
+        if let Some(targets) = self.whispers.remove(&old_id) {
+            self.whispers.insert(new_id, targets);
+        }
+        for targets in self.whispers.values_mut() {
+            if targets.remove(&old_id) {
+                targets.insert(new_id);
+            }
+        }
+    }
+
+    /// Forgets the participant leaving the room. Speakers left without listeners are heard by everybody again.
+    pub fn remove(&mut self, client_id: u64) {
+        self.whispers.remove(&client_id);
+        for targets in self.whispers.values_mut() {
+            targets.remove(&client_id);
+        }
+        self.whispers.retain(|_, targets| !targets.is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub resume_grace_ms: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {resume_grace_ms: 30_000}
+    }
+}
+
+impl SessionConfig {
+    /// Time a participant of a dropped connection is kept in the room waiting for the client to reconnect.
+    pub fn grace_period(&self) -> Duration {
+        Duration::from_millis(self.resume_grace_ms)
+    }
+}
+
+/// Secret given to the client on join to take over its participant after reconnecting.
+pub fn new_resume_token() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 32)
+}
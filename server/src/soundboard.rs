@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::path::Path;
+
+use log::{error, info};
+use rand::Rng;
+use serde::Deserialize;
+
+use easymund_audio_codec::media::MediaDecoder;
+
+const MANIFEST_FILE: &str = "soundboard.yaml";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SoundboardConfig {
+    pub min_interval_ms: u64,
+    pub join_chime: Option<String>,
+    pub leave_chime: Option<String>,
+}
+
+impl Default for SoundboardConfig {
+    fn default() -> Self {
+        Self {min_interval_ms: 3000, join_chime: None, leave_chime: None}
+    }
+}
+
+/// Short sound played once into the room output.
+pub struct SoundEffect {
+    pub id: String,
+    pub name: String,
+    pub names: HashMap<String, String>,
+    pub data: Vec<f32>,
+}
+
+/// Position of a sound effect playing in a room.
+pub struct OneShot {
+    pub effect_id: String,
+    pub position: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SynthKind {
+    Bell,
+    ChimeUp,
+    ChimeDown,
+    Applause,
+    DrumRoll,
+}
+
+#[derive(Deserialize)]
+struct SoundboardManifest {
+    default_language: String,
+    effects: Vec<EffectEntry>,
+}
+
+#[derive(Deserialize)]
+struct EffectEntry {
+    id: String,
+    name: HashMap<String, String>,
+    file: Option<String>,
+    synth: Option<SynthKind>,
+    volume: Option<f32>,
+}
+
+impl SoundEffect {
+    /// Reads `soundboard.yaml` manifest from the sounds dir. Invalid entries are reported and skipped.
+    pub fn read_manifest(path: &str, sample_rate: usize) -> Result<Vec<SoundEffect>, Box<dyn Error>> {
+        let manifest_path = Path::new(path).join(MANIFEST_FILE);
+        let manifest: SoundboardManifest = serde_yaml::from_reader(File::open(&manifest_path)?)?;
+        let mut result = Vec::with_capacity(manifest.effects.len());
+        let mut ids = HashSet::new();
+        for (i, entry) in manifest.effects.iter().enumerate() {
+            if !ids.insert(entry.id.clone()) {
+                error!("Invalid sound effect #{} in {:?}: duplicate id {}", i, &manifest_path, &entry.id);
+                continue;
+            }
+            match SoundEffect::read_entry(path, entry, &manifest.default_language, sample_rate) {
+                Ok(effect) => {
+                    info!("Read sound effect id={}, name={}, length {}", &effect.id, &effect.name, effect.data.len());
+                    result.push(effect);
+                }
+                Err(e) => {
+                    error!("Invalid sound effect #{} id={} in {:?}: {}", i, &entry.id, &manifest_path, e);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn read_entry(path: &str, entry: &EffectEntry, default_language: &str, sample_rate: usize)
+                  -> Result<SoundEffect, Box<dyn Error>> {
+        if entry.id.is_empty() {
+            return Err("empty id")?;
+        }
+        let name = entry.name.get(default_language)
+            .ok_or(format!("no name in default language '{}'", default_language))?;
+        let volume = entry.volume.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(format!("volume {} is out of range 0..1", volume))?;
+        }
+        let data = match (&entry.file, entry.synth) {
+            (Some(file), None) => MediaDecoder::new(sample_rate).decode_file(&Path::new(path).join(file))
+                .map_err(|e| format!("failed to read {}: {}", file, e))?
+                .to_mono(),
+            (None, Some(kind)) => SoundEffect::synthesize(kind, sample_rate as f32),
+            _ => return Err("exactly one of file and synth must be set")?,
+        };
+        Ok(SoundEffect {
+            id: entry.id.clone(),
+            name: name.clone(),
+            names: entry.name.clone(),
+            data: data.iter().map(|v| v * volume).collect(),
+        })
+    }
+
+    fn synthesize(kind: SynthKind, sample_rate: f32) -> Vec<f32> {
+        match kind {
+            SynthKind::Bell => SoundEffect::bell(sample_rate),
+            SynthKind::ChimeUp => SoundEffect::chime(&[660.0, 880.0], sample_rate),
+            SynthKind::ChimeDown => SoundEffect::chime(&[880.0, 660.0], sample_rate),
+            SynthKind::Applause => SoundEffect::applause(sample_rate),
+            SynthKind::DrumRoll => SoundEffect::drum_roll(sample_rate),
+        }
+    }
+
+    fn bell(sample_rate: f32) -> Vec<f32> {
+        let partials = [(1.0, 1.0, 2.5), (2.0, 0.6, 1.6), (2.76, 0.4, 1.1), (5.4, 0.25, 0.6), (8.9, 0.15, 0.3)];
+        let length = (2.5 * sample_rate) as usize;
+        (0..length).map(|i| {
+            let t = i as f32 / sample_rate;
+            let v: f32 = partials.iter()
+                .map(|(ratio, amplitude, decay)| amplitude * (-t / decay).exp() * (2.0 * PI * 880.0 * ratio * t).sin())
+                .sum();
+            v * 0.25
+        }).collect()
+    }
+
+    fn chime(notes: &[f32], sample_rate: f32) -> Vec<f32> {
+        let note_length = (0.18 * sample_rate) as usize;
+        let mut result = vec![0.0; note_length * (notes.len() + 2)];
+        for (n, frequency) in notes.iter().enumerate() {
+            for i in 0..note_length * 3 {
+                let t = i as f32 / sample_rate;
+                let attack = (t / 0.005).min(1.0);
+                result[n * note_length + i] += 0.25 * attack * (-t / 0.15).exp() * (2.0 * PI * frequency * t).sin();
+            }
+        }
+        result
+    }
+
+    fn applause(sample_rate: f32) -> Vec<f32> {
+        let mut rng = rand::thread_rng();
+        let length = (3.0 * sample_rate) as usize;
+        let mut result = vec![0.0; length];
+        let mut band = [0.0_f32; 2];
+        let mut envelope = 0.0_f32;
+        let decay = (-1.0 / (0.008 * sample_rate)).exp();
+        for (i, v) in result.iter_mut().enumerate() {
+            let t = i as f32 / length as f32;
+            let density = (t * 8.0).min(1.0) * ((1.0 - t) * 4.0).min(1.0);
+            if rng.gen::<f32>() < density * 60.0 / sample_rate {
+                envelope = envelope.max(0.3 + 0.7 * rng.gen::<f32>());
+            }
+            envelope *= decay;
+            let white = rng.gen::<f32>() * 2.0 - 1.0;
+            band[0] += 0.5 * (white - band[0]);
+            band[1] += 0.05 * (white - band[1]);
+            *v = (band[0] - band[1]) * envelope * 0.6 + white * density * 0.03;
+        }
+        result
+    }
+
+    fn drum_roll(sample_rate: f32) -> Vec<f32> {
+        let mut rng = rand::thread_rng();
+        let length = (2.5 * sample_rate) as usize;
+        let roll_end = (2.0 * sample_rate) as usize;
+        let stroke = (sample_rate / 18.0) as usize;
+        let mut result = vec![0.0; length];
+        let mut hits: Vec<(usize, f32)> = (0..roll_end / stroke)
+            .map(|n| (n * stroke, 0.2 + 0.5 * n as f32 * stroke as f32 / roll_end as f32 * if n % 2 == 0 { 1.0 } else { 0.85 }))
+            .collect();
+        hits.push((roll_end, 1.0));
+        for (start, amplitude) in hits {
+            for (i, v) in result.iter_mut().skip(start).enumerate().take((0.3 * sample_rate) as usize) {
+                let t = i as f32 / sample_rate;
+                let body = (2.0 * PI * 190.0 * t).sin() * (-t / 0.05).exp();
+                let snare = (rng.gen::<f32>() * 2.0 - 1.0) * (-t / 0.08).exp();
+                *v += amplitude * 0.3 * (0.4 * body + 0.6 * snare);
+            }
+        }
+        result
+    }
+}
+
+impl OneShot {
+    pub fn new(effect_id: &str) -> Self {
+        Self {effect_id: String::from(effect_id), position: 0}
+    }
+
+    /// Renders the next chunk of the effect. Returns `None` when the effect is over.
+    pub fn next_chunk(&mut self, effect: &SoundEffect, samples_count: usize) -> Option<Vec<f32>> {
+        if self.position >= effect.data.len() {
+            return None;
+        }
+        let end = (self.position + samples_count).min(effect.data.len());
+        let mut chunk = Vec::with_capacity(samples_count);
+        chunk.extend_from_slice(&effect.data[self.position..end]);
+        chunk.resize(samples_count, 0.0);
+        self.position = end;
+        Some(chunk)
+    }
+}
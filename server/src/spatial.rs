@@ -0,0 +1,69 @@
+use std::f32::consts::FRAC_PI_4;
+
+use serde::Deserialize;
+
+/// Part of the cutoff radius over which a speaker fades out instead of being cut off abruptly.
+const CUTOFF_FADE: f32 = 0.2;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SpatialConfig {
+    pub reference_distance: f32,
+    pub rolloff: f32,
+    pub cutoff_radius: Option<f32>,
+}
+
+impl Default for SpatialConfig {
+    fn default() -> Self {
+        Self {reference_distance: 1.0, rolloff: 1.0, cutoff_radius: None}
+    }
+}
+
+/// Position of a participant in the room plane. The y axis points down like on the screen,
+/// listeners face up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    pub fn is_valid(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+
+    fn distance(&self, other: &Position) -> f32 {
+        (other.x - self.x).hypot(other.y - self.y)
+    }
+}
+
+impl SpatialConfig {
+    /// Inverse distance attenuation: full level within the reference distance, silence behind the cutoff radius.
+    pub fn gain(&self, listener: &Position, source: &Position) -> f32 {
+        let distance = listener.distance(source);
+        let reference = self.reference_distance.max(f32::EPSILON);
+        let gain = reference / (reference + self.rolloff * (distance - reference).max(0.0));
+        match self.cutoff_radius {
+            Some(radius) => gain * ((radius - distance) / (radius * CUTOFF_FADE)).clamp(0.0, 1.0),
+            None => gain,
+        }
+    }
+
+    /// Left and right gains of the source: distance attenuation with equal power panning by the angle
+    /// between the listener's facing direction and the source.
+    pub fn stereo_gains(&self, listener: &Position, source: &Position) -> (f32, f32) {
+        let gain = self.gain(listener, source);
+        let distance = listener.distance(source);
+        let pan = if distance > f32::EPSILON { (source.x - listener.x) / distance } else { 0.0 };
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        (gain * angle.cos() * 2_f32.sqrt(), gain * angle.sin() * 2_f32.sqrt())
+    }
+}
+
+/// Adds the chunk to the output with the gain changing linearly over the chunk to avoid clicks when people move.
+pub fn mix_ramped(output: &mut [f32], chunk: &[f32], from_gain: f32, to_gain: f32) {
+    let step = (to_gain - from_gain) / output.len().max(1) as f32;
+    for (i, (out, v)) in output.iter_mut().zip(chunk).enumerate() {
+        *out += v * (from_gain + step * i as f32);
+    }
+}
@@ -0,0 +1,28 @@
+use std::error::Error;
+
+use easymund_audio_codec::codec::{Codec, EasymundAudio};
+
+/// Webinar room mode: speakers on the stage are mixed like in a regular room, the listen-only audience
+/// gets the same mix of the whole stage encoded once per tick.
+pub struct Stage {
+    mono_codec: Codec,
+    stereo_codec: Codec,
+}
+
+impl Stage {
+    pub fn new(sample_rate: usize, packet_size: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            mono_codec: EasymundAudio::new(sample_rate, 1, 16).create_codec(packet_size)?,
+            stereo_codec: EasymundAudio::with_channels(sample_rate, 1, 2, 16).create_codec(packet_size)?,
+        })
+    }
+
+    /// Encodes the audience mix for listeners receiving the given number of channels.
+    pub fn encode(&mut self, chunk: &[f32], channels: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+        if channels == 2 {
+            self.stereo_codec.encode(&[chunk, chunk])
+        } else {
+            self.mono_codec.encode(&[chunk])
+        }
+    }
+}
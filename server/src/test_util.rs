@@ -0,0 +1,37 @@
+//! Synthetic signals shared by the audio analysis tests.
+
+use std::f32::consts::PI;
+
+use crate::easymund::SAMPLE_RATE;
+
+pub use crate::procedural::Noise;
+
+pub fn db_to_amplitude(db: f32) -> f32 {
+    10_f32.powf(db / 20.0)
+}
+
+pub fn rms(chunk: &[f32]) -> f32 {
+    (chunk.iter().map(|v| v * v).sum::<f32>() / chunk.len().max(1) as f32).sqrt()
+}
+
+/// Voiced speech: harmonics of a 150 Hz fundamental with a formant-like envelope, syllables of 200 ms
+/// separated by 100 ms gaps. The level is the RMS of the syllables.
+pub fn speech(seconds: f32, level_db: f32) -> Vec<f32> {
+    let raw: Vec<f32> = (0..(seconds * SAMPLE_RATE as f32) as usize).map(|i| {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        let syllable = (t * 1000.0) as usize % 300;
+        if syllable >= 200 {
+            return 0.0;
+        }
+        let envelope = (PI * syllable as f32 / 200.0).sin();
+        let voice: f32 = (1..=20).map(|harmonic| {
+            let frequency = 150.0 * harmonic as f32;
+            let formant = if (400.0..=2500.0).contains(&frequency) { 1.0 } else { 0.3 };
+            formant / harmonic as f32 * (2.0 * PI * frequency * t).sin()
+        }).sum();
+        envelope * voice
+    }).collect();
+    let voiced = raw.iter().filter(|v| **v != 0.0).count().max(1);
+    let level = (raw.iter().map(|v| v * v).sum::<f32>() / voiced as f32).sqrt();
+    raw.iter().map(|v| v / level * db_to_amplitude(level_db)).collect()
+}
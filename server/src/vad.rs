@@ -0,0 +1,291 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+const FRAME_MS: usize = 10;
+const SILENCE_DB: f32 = -100.0;
+const SPEECH_BAND_LOW_HZ: f32 = 300.0;
+const SPEECH_BAND_HIGH_HZ: f32 = 3400.0;
+/// Part of the distance to a quieter frame the noise floor falls by per frame.
+const NOISE_FALL_RATE: f32 = 0.2;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct VadConfig {
+    /// Level above the noise floor a frame needs to be taken for speech.
+    pub snr_db: f32,
+    /// Frames quieter than this are never speech.
+    pub min_level_db: f32,
+    /// Part of the frame energy which has to be in the speech band.
+    pub min_band_ratio: f32,
+    /// Zero crossings per sample above which a frame is taken for hiss or noise.
+    pub max_zero_crossing_rate: f32,
+    pub attack_ms: u64,
+    pub hangover_ms: u64,
+    /// Time constant of the noise floor rising to a louder steady noise.
+    pub noise_rise_ms: u64,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            snr_db: 9.0,
+            min_level_db: -55.0,
+            min_band_ratio: 0.35,
+            max_zero_crossing_rate: 0.25,
+            attack_ms: 30,
+            hangover_ms: 250,
+            noise_rise_ms: 3000,
+        }
+    }
+}
+
+/// Voice activity detector of a participant. The audio is split into 10 ms frames, a frame is speech-like when it is
+/// louder than the adaptive noise floor, has most of its energy in the speech band and does not cross zero as often
+/// as hiss does. The speaker becomes active after `attack_ms` of speech-like frames and stays active for `hangover_ms`
+/// after the last one.
+pub struct Vad {
+    config: VadConfig,
+    frame_size: usize,
+    frame: Vec<f32>,
+    high_pass_alpha: f32,
+    low_pass_alpha: f32,
+    high_pass_input: f32,
+    high_pass_output: f32,
+    low_pass_output: f32,
+    last_sample: f32,
+    noise_floor_db: f32,
+    speech_samples: usize,
+    silence_samples: usize,
+    attack_samples: usize,
+    hangover_samples: usize,
+    is_active: bool,
+}
+
+impl Vad {
+    pub fn new(config: &VadConfig, sample_rate: usize) -> Self {
+        let dt = 1.0 / sample_rate as f32;
+        let high_pass_rc = 1.0 / (2.0 * PI * SPEECH_BAND_LOW_HZ);
+        let low_pass_rc = 1.0 / (2.0 * PI * SPEECH_BAND_HIGH_HZ);
+        Self {
+            config: config.clone(),
+            frame_size: sample_rate * FRAME_MS / 1000,
+            frame: Vec::new(),
+            high_pass_alpha: high_pass_rc / (high_pass_rc + dt),
+            low_pass_alpha: dt / (low_pass_rc + dt),
+            high_pass_input: 0.0,
+            high_pass_output: 0.0,
+            low_pass_output: 0.0,
+            last_sample: 0.0,
+            noise_floor_db: config.min_level_db,
+            speech_samples: 0,
+            silence_samples: 0,
+            attack_samples: config.attack_ms as usize * sample_rate / 1000,
+            hangover_samples: config.hangover_ms as usize * sample_rate / 1000,
+            is_active: false,
+        }
+    }
+
+    /// Analyses the chunk of the tick, missing samples are taken for silence. Returns whether the speaker is active.
+    pub fn process(&mut self, chunk: &[f32], samples_count: usize) -> bool {
+        self.frame.extend_from_slice(chunk);
+        self.frame.resize(self.frame.len() + samples_count.saturating_sub(chunk.len()), 0.0);
+        let mut start = 0;
+        while self.frame.len() - start >= self.frame_size {
+            let frame = self.frame[start..start + self.frame_size].to_vec();
+            let is_speech = self.is_speech_frame(&frame);
+            self.update_state(is_speech);
+            start += self.frame_size;
+        }
+        self.frame.drain(..start);
+        self.is_active
+    }
+
+    /// Current estimate of the background noise level of the participant.
+    pub fn noise_floor_db(&self) -> f32 {
+        self.noise_floor_db
+    }
+
+    fn is_speech_frame(&mut self, frame: &[f32]) -> bool {
+        let mut energy = 0.0;
+        let mut band_energy = 0.0;
+        let mut crossings = 0;
+        for &v in frame {
+            self.high_pass_output = self.high_pass_alpha * (self.high_pass_output + v - self.high_pass_input);
+            self.high_pass_input = v;
+            self.low_pass_output += self.low_pass_alpha * (self.high_pass_output - self.low_pass_output);
+            energy += v * v;
+            band_energy += self.low_pass_output * self.low_pass_output;
+            if (v >= 0.0) != (self.last_sample >= 0.0) {
+                crossings += 1;
+            }
+            self.last_sample = v;
+        }
+        if energy <= 0.0 {
+            return false;
+        }
+        let energy_db = to_db(energy / frame.len() as f32);
+        let band_ratio = band_energy / energy;
+        let zero_crossing_rate = crossings as f32 / frame.len() as f32;
+        let is_speech = energy_db >= self.config.min_level_db
+            && energy_db >= self.noise_floor_db + self.config.snr_db
+            && band_ratio >= self.config.min_band_ratio
+            && zero_crossing_rate <= self.config.max_zero_crossing_rate;
+        self.update_noise_floor(energy_db);
+        is_speech
+    }
+
+    /// The floor follows quieter frames quickly and rises slowly, so steady noise is absorbed within seconds
+    /// while pauses between words keep it at the background level.
+    fn update_noise_floor(&mut self, energy_db: f32) {
+        let rate = if energy_db < self.noise_floor_db {
+            NOISE_FALL_RATE
+        } else {
+            (FRAME_MS as f32 / self.config.noise_rise_ms.max(1) as f32).min(1.0)
+        };
+        self.noise_floor_db = (self.noise_floor_db + (energy_db - self.noise_floor_db) * rate).max(SILENCE_DB);
+    }
+
+    fn update_state(&mut self, is_speech: bool) {
+        if is_speech {
+            self.speech_samples += self.frame_size;
+            self.silence_samples = 0;
+            if self.speech_samples >= self.attack_samples {
+                self.is_active = true;
+            }
+        } else {
+            self.speech_samples = 0;
+            self.silence_samples += self.frame_size;
+            if self.silence_samples > self.hangover_samples {
+                self.is_active = false;
+            }
+        }
+    }
+}
+
+fn to_db(energy: f32) -> f32 {
+    (10.0 * energy.log10()).max(SILENCE_DB)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{db_to_amplitude, rms, speech, Noise};
+
+    const SAMPLE_RATE: usize = 44100;
+    const CHUNK: usize = 2048;
+
+    fn white_noise(seconds: f32, level_db: f32) -> Vec<f32> {
+        let mut noise = Noise::new(7);
+        let amplitude = db_to_amplitude(level_db) * 3_f32.sqrt();
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|_| amplitude * noise.white()).collect()
+    }
+
+    /// Fan rumble: heavily low-passed noise.
+    fn fan_noise(seconds: f32, level_db: f32) -> Vec<f32> {
+        let mut noise = Noise::new(11);
+        let mut state = 0.0;
+        let raw: Vec<f32> = (0..(seconds * SAMPLE_RATE as f32) as usize).map(|_| {
+            state += 0.01 * (noise.white() - state);
+            state
+        }).collect();
+        let level = rms(&raw);
+        raw.iter().map(|v| v / level * db_to_amplitude(level_db)).collect()
+    }
+
+    /// Keyboard: 3 ms clicks of noise every 150 ms.
+    fn keyboard(seconds: f32, level_db: f32) -> Vec<f32> {
+        let mut noise = Noise::new(13);
+        let amplitude = db_to_amplitude(level_db);
+        let click = 3 * SAMPLE_RATE / 1000;
+        let period = 150 * SAMPLE_RATE / 1000;
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|i| {
+            let position = i % period;
+            if position < click { amplitude * noise.white() * (1.0 - position as f32 / click as f32) } else { 0.0 }
+        }).collect()
+    }
+
+    fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+        a.iter().zip(b).map(|(a, b)| a + b).collect()
+    }
+
+    /// Runs the detector over the signal in ticks, returns the part of the ticks the speaker was active.
+    fn activity(vad: &mut Vad, signal: &[f32]) -> f32 {
+        let chunks: Vec<&[f32]> = signal.chunks(CHUNK).collect();
+        let active = chunks.iter().filter(|chunk| vad.process(chunk, CHUNK)).count();
+        active as f32 / chunks.len() as f32
+    }
+
+    fn vad() -> Vad {
+        Vad::new(&VadConfig::default(), SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_speech_is_detected() {
+        assert!(activity(&mut vad(), &speech(2.0, -20.0)) > 0.9);
+    }
+
+    #[test]
+    fn test_quiet_speech_is_detected() {
+        let mut vad = vad();
+        activity(&mut vad, &vec![0.0; SAMPLE_RATE]);
+        assert!(activity(&mut vad, &speech(2.0, -42.0)) > 0.9);
+    }
+
+    #[test]
+    fn test_silence_is_not_speech() {
+        assert_eq!(activity(&mut vad(), &vec![0.0; 2 * SAMPLE_RATE]), 0.0);
+    }
+
+    #[test]
+    fn test_white_noise_is_not_speech() {
+        assert_eq!(activity(&mut vad(), &white_noise(5.0, -25.0)), 0.0);
+    }
+
+    #[test]
+    fn test_fan_noise_is_not_speech() {
+        assert_eq!(activity(&mut vad(), &fan_noise(5.0, -25.0)), 0.0);
+    }
+
+    #[test]
+    fn test_keyboard_is_not_speech() {
+        assert_eq!(activity(&mut vad(), &keyboard(5.0, -15.0)), 0.0);
+    }
+
+    #[test]
+    fn test_noise_floor_adapts() {
+        let mut vad = vad();
+        activity(&mut vad, &white_noise(5.0, -40.0));
+        assert!((vad.noise_floor_db + 40.0).abs() < 3.0, "noise floor {}", vad.noise_floor_db);
+    }
+
+    #[test]
+    fn test_speech_over_fan_noise_is_detected() {
+        let mut vad = vad();
+        activity(&mut vad, &fan_noise(5.0, -35.0));
+        let signal = mix(&speech(2.0, -20.0), &fan_noise(2.0, -35.0));
+        assert!(activity(&mut vad, &signal) > 0.9);
+    }
+
+    #[test]
+    fn test_hangover() {
+        let mut vad = vad();
+        activity(&mut vad, &speech(1.0, -20.0));
+        let silence = vec![0.0; CHUNK];
+        assert!(vad.process(&silence, CHUNK));
+        for _ in 0..10 {
+            vad.process(&silence, CHUNK);
+        }
+        assert!(!vad.is_active);
+    }
+
+    #[test]
+    fn test_missing_audio_is_silence() {
+        let mut vad = vad();
+        activity(&mut vad, &speech(1.0, -20.0));
+        for _ in 0..10 {
+            vad.process(&[], CHUNK);
+        }
+        assert!(!vad.is_active);
+    }
+}
@@ -260,10 +260,9 @@ impl WSServer {
         let path = handshake.path.clone();
         let upgrade = handshake.headers.get(&String::from("Upgrade"));
         let ws_key = handshake.headers.get(&String::from("Sec-WebSocket-Key"));
-        if path.is_none() || upgrade.is_none() || ws_key.is_none() {
-            Err(WSError::new(format!("Invalid handshake {:?}", handshake)))
-        } else {
-            Ok((path.unwrap(), WSServer::generate_handshake_response(&ws_key.unwrap())))
+        match (path, upgrade, ws_key) {
+            (Some(path), Some(_), Some(ws_key)) => Ok((path, WSServer::generate_handshake_response(ws_key))),
+            _ => Err(WSError::new(format!("Invalid handshake {:?}", handshake)))
         }
     }
 
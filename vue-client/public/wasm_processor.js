@@ -6,6 +6,7 @@ class WasmProcessor extends AudioWorkletProcessor {
         this.lib = null;
         this.port.onmessage = (e) => this.onmessage(e.data);
         this.is_muted = false;
+        this.is_echo_cancellation = false;
     }
 
     onmessage(event) {
@@ -13,12 +14,18 @@ class WasmProcessor extends AudioWorkletProcessor {
             this.port.postMessage({type: "audio_log", data: "WASM module received"});
             init(WebAssembly.compile(event.data)).then(() => {
                 this.lib = WasmLib.create();
+                this.lib.set_echo_cancellation(this.is_echo_cancellation);
                 this.port.postMessage({type: "audio_log", data: "WASM lib created"});
             });
         } else if (event.type === "audio_stream") {
             this.lib.receive(event.data);
         } else if (event.type === "audio_mute") {
             this.is_muted = event.value;
+        } else if (event.type === "audio_echo_cancellation") {
+            this.is_echo_cancellation = event.value;
+            if (this.lib) {
+                this.lib.set_echo_cancellation(event.value);
+            }
         }
     }
 
@@ -39,8 +46,7 @@ class WasmProcessor extends AudioWorkletProcessor {
         const output_channel_1 = output[1];
 
         if (this.lib) {
-            const send = this.lib.process(input_channel, output_channel_0);
-            output_channel_1.set(output_channel_0);
+            const send = this.lib.process(input_channel, output_channel_0, output_channel_1);
             if (send) {
                 const buffer = new Uint8Array(4096);
                 const size = this.lib.send(buffer);